@@ -0,0 +1,204 @@
+//! Delegated permissions, as reported by `zfs allow <dataset>`
+
+use crate::ParseError;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Who a set of permissions is granted to
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum Who {
+    User(String),
+    Group(String),
+    Everyone,
+}
+
+/// Which datasets a grant applies to, relative to the dataset it was made on
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Scope {
+    /// -l
+    Local,
+    /// -d
+    Descendent,
+    /// (default)
+    LocalAndDescendent,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Grant {
+    pub who: Who,
+    pub scope: Scope,
+    /// Permission names, properties, and `@set` names
+    pub permissions: BTreeSet<String>,
+}
+
+/// Permissions set directly on a single dataset
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct DatasetPermissions {
+    pub dataset: String,
+    /// Permission sets defined on this dataset, keyed by name (including the leading `@`)
+    pub sets: BTreeMap<String, BTreeSet<String>>,
+    /// Permissions granted to the creator of any descendant dataset
+    pub create_time: BTreeSet<String>,
+    pub grants: Vec<Grant>,
+}
+
+/// Output of `zfs allow <dataset>`: permissions on the dataset itself followed by those on each
+/// of its ancestors which have any permissions set.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Permissions {
+    pub datasets: Vec<DatasetPermissions>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Section {
+    Sets,
+    CreateTime,
+    Grants(Scope),
+}
+
+fn split_perms(perms: &str) -> BTreeSet<String> {
+    perms
+        .split(',')
+        .filter(|p| !p.is_empty())
+        .map(|p| p.to_owned())
+        .collect()
+}
+
+impl Permissions {
+    /// Parse the output of `zfs allow <dataset>`
+    ///
+    /// ```text
+    /// ---- Permissions on tank/backup -------------------------------------
+    /// Permission sets:
+    ///         @backup create,mount,receive
+    /// Local+Descendent permissions:
+    ///         user backup @backup,destroy
+    /// ```
+    pub fn parse(output: &str) -> Result<Self, ParseError> {
+        let mut datasets = Vec::new();
+        let mut current: Option<DatasetPermissions> = None;
+        let mut section = None;
+
+        for line in output.lines() {
+            let err = |msg| ParseError::new(line, msg);
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("---- Permissions on ") {
+                datasets.extend(current.take());
+                let dataset = rest.trim_end_matches('-').trim_end();
+                current = Some(DatasetPermissions {
+                    dataset: dataset.to_owned(),
+                    ..Default::default()
+                });
+                section = None;
+                continue;
+            }
+
+            let ds = current
+                .as_mut()
+                .ok_or_else(|| err("permissions before dataset header"))?;
+
+            if !line.starts_with(char::is_whitespace) {
+                section = Some(match line.trim_end() {
+                    "Permission sets:" => Section::Sets,
+                    "Create time permissions:" => Section::CreateTime,
+                    "Local permissions:" => Section::Grants(Scope::Local),
+                    "Descendent permissions:" => Section::Grants(Scope::Descendent),
+                    "Local+Descendent permissions:" => Section::Grants(Scope::LocalAndDescendent),
+                    _ => return Err(err("unknown section")),
+                });
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            match section.ok_or_else(|| err("entry outside of a section"))? {
+                Section::Sets => {
+                    let name = words.next().ok_or_else(|| err("missing set name"))?;
+                    let perms = words.next().unwrap_or("");
+                    ds.sets.insert(name.to_owned(), split_perms(perms));
+                }
+                Section::CreateTime => {
+                    let perms = words.next().ok_or_else(|| err("missing permissions"))?;
+                    ds.create_time.extend(split_perms(perms));
+                }
+                Section::Grants(scope) => {
+                    let who = match words.next() {
+                        Some("everyone") => Who::Everyone,
+                        Some("user") => {
+                            Who::User(words.next().ok_or_else(|| err("missing user"))?.to_owned())
+                        }
+                        Some("group") => {
+                            Who::Group(words.next().ok_or_else(|| err("missing group"))?.to_owned())
+                        }
+                        _ => return Err(err("unknown grantee")),
+                    };
+                    let perms = words.next().ok_or_else(|| err("missing permissions"))?;
+                    ds.grants.push(Grant {
+                        who,
+                        scope,
+                        permissions: split_perms(perms),
+                    });
+                }
+            }
+        }
+
+        datasets.extend(current);
+        Ok(Permissions { datasets })
+    }
+
+    /// The permissions `user` (a member of `groups`) holds on `dataset`, with permission sets
+    /// expanded. `dataset` must be the dataset these permissions were listed for.
+    pub fn effective(&self, dataset: &str, user: &str, groups: &[&str]) -> BTreeSet<String> {
+        let mut perms = BTreeSet::new();
+
+        for ds in self.datasets.iter() {
+            // every other entry is an ancestor
+            let is_self = ds.dataset == dataset;
+            for grant in ds.grants.iter() {
+                let applies = match grant.scope {
+                    Scope::Local => is_self,
+                    Scope::Descendent => !is_self,
+                    Scope::LocalAndDescendent => true,
+                };
+                let matches = match grant.who {
+                    Who::Everyone => true,
+                    Who::User(ref u) => u == user,
+                    Who::Group(ref g) => groups.contains(&g.as_str()),
+                };
+
+                if applies && matches {
+                    perms.extend(grant.permissions.iter().cloned());
+                }
+            }
+        }
+
+        // sets may contain other sets, so expand until none remain. Each set is expanded once,
+        // which also stops a set that (indirectly) contains itself.
+        let mut expanded = BTreeSet::new();
+        loop {
+            let sets: Vec<String> = perms
+                .iter()
+                .filter(|p| p.starts_with('@'))
+                .cloned()
+                .collect();
+            if sets.is_empty() {
+                break;
+            }
+            for set in sets {
+                perms.remove(&set);
+                if !expanded.insert(set.clone()) {
+                    continue;
+                }
+                // sets are visible on the dataset they are defined on and its descendants, so
+                // the nearest definition wins
+                if let Some(members) = self.datasets.iter().find_map(|ds| ds.sets.get(&set)) {
+                    perms.extend(members.iter().cloned());
+                }
+            }
+        }
+
+        perms
+    }
+}
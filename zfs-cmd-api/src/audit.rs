@@ -0,0 +1,115 @@
+//! Append-only record of state changing `zfs` operations.
+//!
+//! Each operation is written as a single line of JSON once its outcome is known. Records are
+//! written with a single `write` to a file opened in append mode, so multiple processes may share
+//! one log.
+
+use serde_derive::Serialize;
+use std::ffi::OsStr;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+#[derive(Debug, Serialize)]
+pub struct AuditRecord {
+    /// Seconds since the unix epoch at which the operation was started
+    pub time: u64,
+    pub user: Option<String>,
+    pub pid: u32,
+    pub argv: Vec<String>,
+    pub outcome: Outcome,
+    /// Snapshots of the affected dataset which existed prior to the operation, if the operation
+    /// could remove any. Not recorded for channel programs.
+    pub snapshots_before: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case", tag = "result")]
+pub enum Outcome {
+    Success,
+    Failed {
+        error: String,
+    },
+    /// The operation was started, but we never learned how it completed (for example, a
+    /// send/recv pipeline that was abandoned part way through).
+    Unknown,
+}
+
+/// An operation which has been started but whose outcome has not yet been recorded.
+///
+/// If dropped without calling `finish()`, the record is written with `Outcome::Unknown`.
+#[derive(Debug)]
+pub(crate) struct Pending {
+    file: File,
+    record: Option<AuditRecord>,
+}
+
+impl Pending {
+    /// Open the log at `path` prior to running `cmd`.
+    ///
+    /// Opening happens before the command is run so that an unwritable log prevents the
+    /// operation rather than silently losing the record of it.
+    pub(crate) fn open(
+        path: &Path,
+        cmd: &process::Command,
+        snapshots_before: Option<Vec<String>>,
+    ) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+
+        let argv = std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|a: &OsStr| a.to_string_lossy().into_owned())
+            .collect();
+
+        Ok(Pending {
+            file,
+            record: Some(AuditRecord {
+                time: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                user: std::env::var("USER")
+                    .or_else(|_| std::env::var("LOGNAME"))
+                    .ok(),
+                pid: process::id(),
+                argv,
+                outcome: Outcome::Unknown,
+                snapshots_before,
+            }),
+        })
+    }
+
+    pub(crate) fn finish(mut self, outcome: Outcome) {
+        self.write(outcome);
+    }
+
+    fn write(&mut self, outcome: Outcome) {
+        let mut record = match self.record.take() {
+            Some(r) => r,
+            None => return,
+        };
+        record.outcome = outcome;
+
+        // the command has already run at this point, so all we can do is complain
+        let mut line = match serde_json::to_vec(&record) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("could not encode audit record {:?}: {}", record, e);
+                return;
+            }
+        };
+        line.push(b'\n');
+        if let Err(e) = self.file.write_all(&line) {
+            warn!("could not write audit record {:?}: {}", record, e);
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.write(Outcome::Unknown);
+    }
+}
@@ -0,0 +1,132 @@
+//! Helpers for destroying many snapshots of a filesystem with few `zfs destroy` invocations
+
+use std::collections::{BTreeSet, HashMap};
+
+/// Longest single argument we'll pass to `zfs destroy`.
+///
+/// Linux limits each argument to 128KiB (`MAX_ARG_STRLEN`), stay well under it.
+pub const MAX_ARG_LEN: usize = 64 * 1024;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DestroyFailure {
+    /// Full snapshot name (`fs@snap`)
+    pub snapshot: String,
+    pub reason: String,
+}
+
+/// Outcome of `Zfs::destroy_snapshots()`
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct DestroyReport {
+    /// Full names (`fs@snap`) of the snapshots destroyed (or, in a dry run, that would be)
+    pub destroyed: Vec<String>,
+    pub failed: Vec<DestroyFailure>,
+}
+
+/// Form the snapshot part of `zfs destroy` arguments for `snapshots`, using ranges (`a%d`) for
+/// runs of 3 or more snapshots which are adjacent in `order`.
+///
+/// `order` must list every snapshot of the filesystem in `createtxg` order, as `zfs` destroys
+/// every snapshot created between the ends of a range. Pass an empty `order` to never use ranges.
+/// Snapshots not present in `order` are named individually.
+pub fn snapshot_specs(snapshots: &[&str], order: &[&str]) -> Vec<String> {
+    let wanted: BTreeSet<&str> = snapshots.iter().copied().collect();
+    let mut specs = Vec::new();
+
+    let mut flush = |run: &mut Vec<&str>| {
+        if run.len() >= 3 {
+            specs.push(format!("{}%{}", run[0], run[run.len() - 1]));
+        } else {
+            specs.extend(run.iter().map(|s| (*s).to_owned()));
+        }
+        run.clear();
+    };
+
+    let mut run = Vec::new();
+    for snap in order.iter() {
+        if wanted.contains(snap) {
+            run.push(*snap);
+        } else {
+            flush(&mut run);
+        }
+    }
+    flush(&mut run);
+
+    let ordered: BTreeSet<&str> = order.iter().copied().collect();
+    let mut seen = BTreeSet::new();
+    for snap in snapshots.iter() {
+        if !ordered.contains(snap) && seen.insert(*snap) {
+            specs.push((*snap).to_owned());
+        }
+    }
+
+    specs
+}
+
+/// Deduplicate `snapshots` and sort them by their position in `order`, placing any not in `order`
+/// last (in the order given).
+pub fn sort_by_order<'a>(snapshots: &[&'a str], order: &[&str]) -> Vec<&'a str> {
+    let mut positions: HashMap<&str, usize> = HashMap::with_capacity(order.len());
+    for (i, o) in order.iter().enumerate() {
+        positions.entry(*o).or_insert(i);
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut sorted: Vec<(usize, &'a str)> = snapshots
+        .iter()
+        .filter(|s| seen.insert(**s))
+        .map(|s| (positions.get(s).copied().unwrap_or(order.len()), *s))
+        .collect();
+    sorted.sort_by_key(|(pos, _)| *pos);
+    sorted.into_iter().map(|(_, s)| s).collect()
+}
+
+/// Split `snapshots` into groups which, named individually, fit in a single `zfs destroy`
+/// argument (`fs@a,b,c`) no longer than `max_len`. Forming ranges within a group only shortens
+/// the argument.
+pub fn chunk_snapshots<'a>(
+    filesystem: &str,
+    snapshots: &[&'a str],
+    max_len: usize,
+) -> Vec<Vec<&'a str>> {
+    let mut chunks = Vec::new();
+    let mut chunk: Vec<&'a str> = Vec::new();
+    let mut len = filesystem.len() + 1;
+
+    for snap in snapshots.iter() {
+        if !chunk.is_empty() && len + 1 + snap.len() > max_len {
+            chunks.push(std::mem::take(&mut chunk));
+            len = filesystem.len() + 1;
+        }
+
+        if !chunk.is_empty() {
+            len += 1;
+        }
+        len += snap.len();
+        chunk.push(*snap);
+    }
+
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    chunks
+}
+
+/// Extract the per-snapshot failures from the stderr of a failed `zfs destroy`
+///
+/// ```text
+/// cannot destroy snapshot tank/home@a: dataset is busy
+/// ```
+pub fn parse_failures(stderr: &str) -> Vec<DestroyFailure> {
+    stderr
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("cannot destroy snapshot ")?;
+            let (snapshot, reason) = rest.split_once(": ")?;
+            Some(DestroyFailure {
+                snapshot: snapshot.to_owned(),
+                reason: reason.to_owned(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,156 @@
+//! Records emitted by `zfs diff -FHt`
+
+use crate::ParseError;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChangeType {
+    /// `-`
+    Removed,
+    /// `+`
+    Created,
+    /// `M`
+    Modified,
+    /// `R`
+    Renamed,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FileType {
+    /// `B`
+    BlockDevice,
+    /// `C`
+    CharacterDevice,
+    /// `/`
+    Directory,
+    /// `>`
+    Door,
+    /// `|`
+    Fifo,
+    /// `@`
+    Symlink,
+    /// `P`
+    EventPort,
+    /// `=`
+    Socket,
+    /// `F`
+    File,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DiffRecord {
+    /// Inode change time of the file
+    pub time: SystemTime,
+    pub change: ChangeType,
+    pub file_type: FileType,
+    pub path: PathBuf,
+    /// For `ChangeType::Renamed`, the new path
+    pub new_path: Option<PathBuf>,
+}
+
+/// Undo the escaping `zfs diff` applies to paths: any byte that is not a printable, non-space
+/// ascii character (and `\` itself) is written as `\` followed by 4 octal digits.
+pub fn unescape_path(escaped: &[u8]) -> Result<PathBuf, &'static str> {
+    let mut out = Vec::with_capacity(escaped.len());
+    let mut i = 0;
+    while i < escaped.len() {
+        if escaped[i] != b'\\' {
+            out.push(escaped[i]);
+            i += 1;
+            continue;
+        }
+
+        let digits = escaped
+            .get(i + 1..i + 5)
+            .ok_or("truncated escape sequence")?;
+        let mut v: u32 = 0;
+        for d in digits {
+            if !(b'0'..=b'7').contains(d) {
+                return Err("invalid escape sequence");
+            }
+            v = v * 8 + u32::from(d - b'0');
+        }
+        if v > 0xff {
+            return Err("escape sequence out of range");
+        }
+        out.push(v as u8);
+        i += 5;
+    }
+
+    Ok(PathBuf::from(OsString::from_vec(out)))
+}
+
+fn parse_time(t: &str) -> Option<SystemTime> {
+    let (secs, nanos) = match t.split_once('.') {
+        Some((s, n)) => (s, n),
+        None => (t, "0"),
+    };
+    let secs: u64 = secs.parse().ok()?;
+    let nanos: u32 = nanos.parse().ok()?;
+    UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+}
+
+impl DiffRecord {
+    /// Parse a single line (without the trailing newline) of `zfs diff -FHt` output
+    ///
+    /// ```text
+    /// 1697000000.123456789    M    /    /tank/home
+    /// 1697000000.123456789    R    F    /tank/home/a    /tank/home/b
+    /// ```
+    pub fn parse_line(line: &[u8]) -> Result<Self, ParseError> {
+        let err = |msg| ParseError::new(&String::from_utf8_lossy(line), msg);
+        let mut fields = line.split(|&b| b == b'\t');
+
+        let mut next = |msg| fields.next().ok_or_else(|| err(msg));
+        let time = next("missing time")?;
+        let change = next("missing change type")?;
+        let file_type = next("missing file type")?;
+        let path = next("missing path")?;
+        let new_path = fields.next();
+
+        let time = std::str::from_utf8(time)
+            .ok()
+            .and_then(parse_time)
+            .ok_or_else(|| err("invalid time"))?;
+
+        let change = match change {
+            b"-" => ChangeType::Removed,
+            b"+" => ChangeType::Created,
+            b"M" => ChangeType::Modified,
+            b"R" => ChangeType::Renamed,
+            _ => return Err(err("unknown change type")),
+        };
+
+        let file_type = match file_type {
+            b"B" => FileType::BlockDevice,
+            b"C" => FileType::CharacterDevice,
+            b"/" => FileType::Directory,
+            b">" => FileType::Door,
+            b"|" => FileType::Fifo,
+            b"@" => FileType::Symlink,
+            b"P" => FileType::EventPort,
+            b"=" => FileType::Socket,
+            b"F" => FileType::File,
+            _ => return Err(err("unknown file type")),
+        };
+
+        let path = unescape_path(path).map_err(err)?;
+        let new_path = match (change, new_path) {
+            (ChangeType::Renamed, Some(p)) => Some(unescape_path(p).map_err(err)?),
+            (ChangeType::Renamed, None) => return Err(err("rename without new path")),
+            (_, Some(_)) => return Err(err("unexpected new path")),
+            (_, None) => None,
+        };
+
+        Ok(DiffRecord {
+            time,
+            change,
+            file_type,
+            path,
+            new_path,
+        })
+    }
+}
@@ -0,0 +1,95 @@
+//! Native encryption state of datasets
+
+use crate::ParseError;
+
+/// `keystatus` property
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum KeyStatus {
+    Available,
+    Unavailable,
+}
+
+/// Encryption related properties of a single dataset
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EncryptionInfo {
+    pub name: String,
+    /// Cipher suite, `None` when encryption is `off`
+    pub encryption: Option<String>,
+    /// The dataset whose key is used to encrypt this one
+    pub encryption_root: Option<String>,
+    /// `None` for unencrypted datasets
+    pub key_status: Option<KeyStatus>,
+    /// Only present on encryption roots (ie: `prompt`, `file:///path`)
+    pub key_location: Option<String>,
+    /// `raw`, `hex`, or `passphrase`; `None` for unencrypted datasets
+    pub key_format: Option<String>,
+}
+
+/// The properties to request from `zfs list` in the order `EncryptionInfo::from_list_row` expects
+pub const LIST_ELEMENTS: &[&str] = &[
+    "name",
+    "encryption",
+    "encryptionroot",
+    "keystatus",
+    "keylocation",
+    "keyformat",
+];
+
+fn unset(v: &str, none: &str) -> Option<String> {
+    if v == "-" || v == none || v.is_empty() {
+        None
+    } else {
+        Some(v.to_owned())
+    }
+}
+
+impl EncryptionInfo {
+    /// Parse a row of `zfs list -pH -o <LIST_ELEMENTS>`
+    pub fn from_list_row(row: &[String]) -> Result<Self, ParseError> {
+        let err = |msg| ParseError::new(&row.join("\t"), msg);
+        if row.len() != LIST_ELEMENTS.len() {
+            return Err(err("unexpected number of columns"));
+        }
+
+        let key_status = match row[3].as_str() {
+            "available" => Some(KeyStatus::Available),
+            "unavailable" => Some(KeyStatus::Unavailable),
+            "-" | "none" => None,
+            _ => return Err(err("unknown keystatus")),
+        };
+
+        Ok(EncryptionInfo {
+            name: row[0].clone(),
+            encryption: unset(&row[1], "off"),
+            encryption_root: unset(&row[2], "-"),
+            key_status,
+            key_location: unset(&row[4], "none"),
+            key_format: unset(&row[5], "none"),
+        })
+    }
+
+    pub fn is_encryption_root(&self) -> bool {
+        self.encryption_root.as_deref() == Some(self.name.as_str())
+    }
+}
+
+/// Where `zfs load-key` and `zfs change-key` obtain key material from
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum KeySource<'a> {
+    /// Use the dataset's `keylocation` property
+    Property,
+    /// A `keylocation` style uri (ie: `file:///path/to/key`)
+    Location(&'a str),
+    /// Key material passed to `zfs` on stdin (`-L prompt`), formatted according to `keyformat`
+    Data(&'a [u8]),
+}
+
+#[enumflags2::bitflags]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoadKeyFlags {
+    /// -r: also load keys of descendant encryption roots
+    Recursive = 1 << 0,
+    /// -n: only check that the key is correct
+    DryRun = 1 << 1,
+}
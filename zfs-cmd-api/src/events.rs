@@ -0,0 +1,271 @@
+//! Parsing the verbose (`-Hv`) output of `zpool events`
+
+use crate::ParseError;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A value in an event's payload
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EventValue {
+    /// Integers of every width (printed in hex)
+    Number(u64),
+    String(String),
+    Numbers(Vec<u64>),
+    Strings(Vec<String>),
+    Nvlist(BTreeMap<String, EventValue>),
+    Nvlists(Vec<BTreeMap<String, EventValue>>),
+    /// Anything else, as printed (booleans, doubles)
+    Other(String),
+}
+
+impl EventValue {
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            EventValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            EventValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Kinds of event worth reacting to, as identified by their class
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EventKind {
+    /// `ereport.fs.zfs.checksum`
+    ChecksumError,
+    /// `ereport.fs.zfs.io`
+    IoError,
+    /// `resource.fs.zfs.removed`
+    DeviceRemoved,
+    /// `resource.fs.zfs.statechange`
+    StateChange,
+    ScrubStart,
+    ScrubFinish,
+    ResilverStart,
+    ResilverFinish,
+    PoolImport,
+    Other,
+}
+
+/// An event from `zpool events -v`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ZpoolEvent {
+    /// e.g. `sysevent.fs.zfs.scrub_finish`
+    pub class: String,
+    /// When the event was posted, from the payload's `time`
+    pub time: Option<SystemTime>,
+    /// Event id (`eid`)
+    pub eid: Option<u64>,
+    /// From `pool` (ereports) or `pool_name` (sysevents)
+    pub pool: Option<String>,
+    pub vdev_guid: Option<u64>,
+    pub vdev_path: Option<String>,
+    /// Every field of the event, including those above
+    pub payload: BTreeMap<String, EventValue>,
+}
+
+impl ZpoolEvent {
+    pub fn kind(&self) -> EventKind {
+        match self.class.as_str() {
+            "ereport.fs.zfs.checksum" => EventKind::ChecksumError,
+            "ereport.fs.zfs.io" => EventKind::IoError,
+            "resource.fs.zfs.removed" => EventKind::DeviceRemoved,
+            "resource.fs.zfs.statechange" => EventKind::StateChange,
+            "sysevent.fs.zfs.scrub_start" => EventKind::ScrubStart,
+            "sysevent.fs.zfs.scrub_finish" => EventKind::ScrubFinish,
+            "sysevent.fs.zfs.resilver_start" => EventKind::ResilverStart,
+            "sysevent.fs.zfs.resilver_finish" => EventKind::ResilverFinish,
+            "sysevent.fs.zfs.pool_import" => EventKind::PoolImport,
+            _ => EventKind::Other,
+        }
+    }
+
+    fn from_payload(
+        class: String,
+        payload: BTreeMap<String, EventValue>,
+    ) -> Result<Self, ParseError> {
+        let s = |k: &str| payload.get(k).and_then(|v| v.as_str()).map(str::to_owned);
+        let n = |k: &str| payload.get(k).and_then(|v| v.as_u64());
+
+        let time = match payload.get("time") {
+            Some(EventValue::Numbers(t)) if t.len() == 2 => {
+                Some(UNIX_EPOCH + Duration::from_secs(t[0]) + Duration::from_nanos(t[1]))
+            }
+            _ => None,
+        };
+
+        Ok(ZpoolEvent {
+            time,
+            eid: n("eid"),
+            pool: s("pool").or_else(|| s("pool_name")),
+            vdev_guid: n("vdev_guid"),
+            vdev_path: s("vdev_path"),
+            class,
+            payload,
+        })
+    }
+}
+
+/// Parse a printed nvpair value. `rest` holds the lines following it, for embedded nvlists.
+fn parse_value<'a, I>(name: &str, value: &str, rest: &mut I) -> Result<EventValue, ParseError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    if value == "(embedded nvlist)" {
+        return Ok(EventValue::Nvlist(parse_nvlist(rest, Some(name))?));
+    }
+
+    if let Some(count) = value
+        .strip_prefix('(')
+        .and_then(|v| v.strip_suffix(" embedded nvlists)"))
+    {
+        let count: usize = count
+            .parse()
+            .map_err(|_| ParseError::new(value, "invalid nvlist count"))?;
+        let mut lists = Vec::with_capacity(count);
+        for i in 0..count {
+            let elem = format!("{}[{}]", name, i);
+            let line = rest
+                .next()
+                .ok_or_else(|| ParseError::new(value, "missing nvlist array element"))?;
+            if line.trim() != format!("{} = (embedded nvlist)", elem) {
+                return Err(ParseError::new(line, "expected nvlist array element"));
+            }
+            lists.push(parse_nvlist(rest, Some(&elem))?);
+        }
+        return match rest.next().map(str::trim) {
+            Some(end) if end == format!("(end {})", name) => Ok(EventValue::Nvlists(lists)),
+            _ => Err(ParseError::new(value, "unterminated nvlist array")),
+        };
+    }
+
+    // arrays are printed with a space after each element, scalars without
+    let array = value.ends_with(' ');
+    let value = value.trim_end();
+
+    if value.starts_with('"') {
+        let inner = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .ok_or_else(|| ParseError::new(value, "unterminated string"))?;
+        return Ok(if array {
+            EventValue::Strings(inner.split("\" \"").map(str::to_owned).collect())
+        } else {
+            EventValue::String(inner.to_owned())
+        });
+    }
+
+    let hex = |v: &str| {
+        v.strip_prefix("0x")
+            .and_then(|h| u64::from_str_radix(h, 16).ok())
+    };
+    if array {
+        if let Some(nums) = value.split(' ').map(hex).collect::<Option<Vec<_>>>() {
+            return Ok(EventValue::Numbers(nums));
+        }
+    } else if let Some(n) = hex(value) {
+        return Ok(EventValue::Number(n));
+    }
+
+    Ok(EventValue::Other(value.to_owned()))
+}
+
+/// Parse `name = value` lines until `(end <end>)` (for embedded nvlists) or the input ends
+fn parse_nvlist<'a, I>(
+    lines: &mut I,
+    end: Option<&str>,
+) -> Result<BTreeMap<String, EventValue>, ParseError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut nvlist = BTreeMap::new();
+    while let Some(line) = lines.next() {
+        let line = line.trim_start();
+        if let Some(end) = end {
+            if line.trim_end() == format!("(end {})", end) {
+                return Ok(nvlist);
+            }
+        }
+
+        let (name, value) = line
+            .split_once(" = ")
+            .ok_or_else(|| ParseError::new(line, "expected name = value"))?;
+        let value = parse_value(name, value, lines)?;
+        nvlist.insert(name.to_owned(), value);
+    }
+
+    match end {
+        Some(_) => Err(ParseError::new("", "unterminated nvlist")),
+        None => Ok(nvlist),
+    }
+}
+
+/// Parse one event: a header line (`<time>\t<class>`) followed by its payload
+///
+/// ```text
+/// Oct 18 2026 03:12:44.318040521    sysevent.fs.zfs.scrub_finish
+///         version = 0x0
+///         class = "sysevent.fs.zfs.scrub_finish"
+///         pool_name = "tank"
+///         time = 0x68f304dc 0x12f4c4c9
+///         eid = 0x2a
+/// ```
+pub fn parse_event(text: &str) -> Result<ZpoolEvent, ParseError> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let header = lines
+        .next()
+        .ok_or_else(|| ParseError::new(text, "empty event"))?;
+    let header_class = header.rsplit(['\t', ' ']).next().unwrap_or("");
+
+    let payload = parse_nvlist(&mut lines, None)?;
+    let class = payload
+        .get("class")
+        .and_then(|c| c.as_str())
+        .unwrap_or(header_class)
+        .to_owned();
+    if class.is_empty() {
+        return Err(ParseError::new(header, "no event class"));
+    }
+
+    ZpoolEvent::from_payload(class, payload)
+}
+
+/// Splits the output of `zpool events -Hv` into events, one line at a time
+#[derive(Debug, Clone, Default)]
+pub struct EventParser {
+    pending: String,
+}
+
+impl EventParser {
+    /// Add a line of output. Returns the event it completes, if any.
+    ///
+    /// Events are terminated by a blank line, or by the next event's (unindented) header.
+    pub fn parse_line(&mut self, line: &str) -> Result<Option<ZpoolEvent>, ParseError> {
+        let is_header = !line.is_empty() && !line.starts_with(char::is_whitespace);
+        let mut event = None;
+        if (line.trim().is_empty() || is_header) && !self.pending.is_empty() {
+            event = self.finish()?;
+        }
+
+        if !line.trim().is_empty() {
+            self.pending.push_str(line);
+            self.pending.push('\n');
+        }
+        Ok(event)
+    }
+
+    /// Parse any event not yet terminated, for use once the output ends
+    pub fn finish(&mut self) -> Result<Option<ZpoolEvent>, ParseError> {
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+        let pending = std::mem::take(&mut self.pending);
+        parse_event(&pending).map(Some)
+    }
+}
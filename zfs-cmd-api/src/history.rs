@@ -0,0 +1,331 @@
+//! Parsing `zpool history -il`: the commands run against a pool, and the internal events they
+//! caused
+
+use crate::ParseError;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum HistoryEvent {
+    /// A command run by a user, as typed (`zfs snapshot tank/home@a`)
+    Command(String),
+    /// A change made within a txg (`snapshot`, `destroy`, `set`, etc)
+    Internal {
+        event: String,
+        /// The dataset affected, if any
+        dataset: Option<String>,
+        /// The objset id of `dataset`
+        dataset_id: Option<u64>,
+        /// Event specific detail, like `compression=2` for `set`
+        detail: String,
+    },
+    /// An ioctl issued by a command, with its input and output printed as nvlists
+    Ioctl { name: String, detail: String },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryRecord {
+    /// To the second
+    pub time: SystemTime,
+    /// Only for internal events
+    pub txg: Option<u64>,
+    pub event: HistoryEvent,
+    pub uid: Option<u32>,
+    /// User name, when it could be determined
+    pub user: Option<String>,
+    pub host: Option<String>,
+    pub zone: Option<String>,
+}
+
+/// Is `name` the dataset `dataset` or a descendant, snapshot, or bookmark of it?
+fn within(name: &str, dataset: &str) -> bool {
+    match name.strip_prefix(dataset) {
+        Some("") => true,
+        Some(rest) => rest.starts_with(['/', '@', '#']),
+        None => false,
+    }
+}
+
+impl HistoryRecord {
+    /// Whether this record concerns `dataset` (or its descendants, snapshots, and bookmarks),
+    /// judged by the dataset of internal events and the words of commands and ioctl details
+    pub fn mentions(&self, dataset: &str) -> bool {
+        let words = |text: &str| {
+            text.split_whitespace()
+                .flat_map(|w| w.split(','))
+                .any(|w| within(w, dataset))
+        };
+
+        match self.event {
+            HistoryEvent::Command(ref cmd) => words(cmd),
+            HistoryEvent::Internal {
+                dataset: Some(ref name),
+                ..
+            } => within(name, dataset),
+            HistoryEvent::Internal { .. } => false,
+            HistoryEvent::Ioctl { ref detail, .. } => words(detail),
+        }
+    }
+}
+
+/// Select the records mentioning `dataset` (if given) with times within `since..until`
+pub fn filter<'a>(
+    records: &'a [HistoryRecord],
+    dataset: Option<&str>,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+) -> Vec<&'a HistoryRecord> {
+    records
+        .iter()
+        .filter(|r| match dataset {
+            Some(d) => r.mentions(d),
+            None => true,
+        })
+        .filter(|r| match since {
+            Some(t) => r.time >= t,
+            None => true,
+        })
+        .filter(|r| match until {
+            Some(t) => r.time < t,
+            None => true,
+        })
+        .collect()
+}
+
+/// Days from 1970-01-01 to the given (proleptic Gregorian) date
+pub(crate) fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let m = m as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// The date `days` after 1970-01-01, as `(year, month, day)`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+/// Format `time` as `zpool history` does (`2020-10-11.00:24:01`), in UTC
+pub fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (y, m, d) = civil_from_days((secs / (24 * 60 * 60)) as i64);
+    let s = secs % (24 * 60 * 60);
+    format!(
+        "{:04}-{:02}-{:02}.{:02}:{:02}:{:02}",
+        y,
+        m,
+        d,
+        s / 3600,
+        s / 60 % 60,
+        s % 60
+    )
+}
+
+/// Parse a UTC `2020-10-11.00:24:01` timestamp
+fn parse_time(v: &str) -> Option<SystemTime> {
+    let (date, time) = v.split_once('.')?;
+    let mut date = date.splitn(3, '-').map(|p| p.parse::<u32>().ok());
+    let (y, m, d) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.splitn(3, ':').map(|p| p.parse::<u64>().ok());
+    let (hh, mm, ss) = (time.next()??, time.next()??, time.next()??);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) || hh > 23 || mm > 59 || ss > 60 {
+        return None;
+    }
+
+    let days = days_from_civil(y as i64, m, d);
+    let secs = u64::try_from(days).ok()? * 24 * 60 * 60 + hh * 60 * 60 + mm * 60 + ss;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Split the `[user 0 (root) on host:zone]` suffix from a record
+fn split_origin(text: &str) -> (&str, Option<&str>) {
+    let trimmed = text.trim_end();
+    if trimmed.ends_with(']') {
+        if let Some(start) = trimmed.rfind(" [") {
+            let inner = &trimmed[start + 2..trimmed.len() - 1];
+            if inner.starts_with("user ") || inner.starts_with("on ") {
+                return (&trimmed[..start], Some(inner));
+            }
+        }
+    }
+    (text, None)
+}
+
+fn parse_internal(rest: &str, line: &str) -> Result<(u64, HistoryEvent), ParseError> {
+    let err = |msg| ParseError::new(line, msg);
+
+    // old style: `[internal <event> txg:<txg>] <detail>`
+    if let Some(rest) = rest.strip_prefix("[internal ") {
+        let (head, detail) = rest.split_once(']').ok_or_else(|| err("unterminated ["))?;
+        let (event, txg) = head
+            .rsplit_once(" txg:")
+            .ok_or_else(|| err("missing txg"))?;
+        let txg = txg.parse().map_err(|_| err("invalid txg"))?;
+        return Ok((
+            txg,
+            HistoryEvent::Internal {
+                event: event.to_owned(),
+                dataset: None,
+                dataset_id: None,
+                detail: detail.trim().to_owned(),
+            },
+        ));
+    }
+
+    // `[txg:<txg>] <event> [<dataset> (<id>)] <detail>`
+    let rest = rest
+        .strip_prefix("[txg:")
+        .ok_or_else(|| err("expected txg"))?;
+    let (txg, rest) = rest.split_once("] ").ok_or_else(|| err("unterminated ["))?;
+    let txg = txg.parse().map_err(|_| err("invalid txg"))?;
+    let (event, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+
+    let mut dataset = None;
+    let mut dataset_id = None;
+    let mut detail = rest;
+    // dataset names may contain spaces, so look for the ` (<id>)` which follows the name
+    let mut search = 0;
+    while let Some(i) = rest[search..].find(" (") {
+        let open = search + i;
+        let after = &rest[open + 2..];
+        if let Some(close) = after.find(')') {
+            if let Ok(id) = after[..close].parse() {
+                dataset = Some(rest[..open].to_owned());
+                dataset_id = Some(id);
+                detail = &after[close + 1..];
+                break;
+            }
+        }
+        search = open + 2;
+    }
+
+    Ok((
+        txg,
+        HistoryEvent::Internal {
+            event: event.to_owned(),
+            dataset,
+            dataset_id,
+            detail: detail.trim().to_owned(),
+        },
+    ))
+}
+
+fn parse_record(text: &str) -> Result<HistoryRecord, ParseError> {
+    let err = |msg| ParseError::new(text, msg);
+    let (time, rest) = text.split_once(' ').ok_or_else(|| err("missing time"))?;
+    let time = parse_time(time).ok_or_else(|| err("invalid time"))?;
+
+    let (body, origin) = split_origin(rest);
+    let mut record = HistoryRecord {
+        time,
+        txg: None,
+        event: HistoryEvent::Command(String::new()),
+        uid: None,
+        user: None,
+        host: None,
+        zone: None,
+    };
+
+    if body.starts_with('[') {
+        let first = body.lines().next().unwrap_or(body);
+        let (txg, event) = parse_internal(first, text)?;
+        record.txg = Some(txg);
+        record.event = event;
+    } else if let Some(ioctl) = body.strip_prefix("ioctl ") {
+        let (name, detail) = ioctl.split_once('\n').unwrap_or((ioctl, ""));
+        record.event = HistoryEvent::Ioctl {
+            name: name.trim().to_owned(),
+            detail: detail.trim_end().to_owned(),
+        };
+    } else {
+        record.event = HistoryEvent::Command(body.trim().to_owned());
+    }
+
+    // `user <uid> [(<name>)] on <host>[:<zone>]`
+    if let Some(origin) = origin {
+        let mut words = origin.split(' ').peekable();
+        while let Some(word) = words.next() {
+            match word {
+                "user" => {
+                    record.uid = words.next().and_then(|u| u.parse().ok());
+                    if let Some(name) = words
+                        .peek()
+                        .and_then(|n| n.strip_prefix('(')?.strip_suffix(')'))
+                    {
+                        record.user = Some(name.to_owned());
+                        words.next();
+                    }
+                }
+                "on" => {
+                    if let Some(host) = words.next() {
+                        let (host, zone) = match host.split_once(':') {
+                            Some((h, z)) => (h, Some(z.to_owned())),
+                            None => (host, None),
+                        };
+                        record.host = Some(host.to_owned());
+                        record.zone = zone;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(record)
+}
+
+fn starts_record(line: &str) -> bool {
+    line.split(' ')
+        .next()
+        .is_some_and(|t| parse_time(t).is_some())
+}
+
+/// Parse the output of `zpool history -il`, run with `TZ=UTC` so times are printed in UTC
+///
+/// ```text
+/// History for 'tank':
+/// 2020-10-11.00:30:12 [txg:120] snapshot tank/home@a (390)  [on host1]
+/// 2020-10-11.00:30:12 zfs snapshot tank/home@a [user 1000 (cody) on host1:linux]
+/// ```
+pub fn parse_history(output: &str) -> Result<Vec<HistoryRecord>, ParseError> {
+    let mut records = Vec::new();
+    let mut pending = String::new();
+
+    for line in output.lines() {
+        if line.starts_with("History for ") {
+            continue;
+        }
+        if starts_record(line) && !pending.is_empty() {
+            records.push(parse_record(&pending)?);
+            pending.clear();
+        }
+        if pending.is_empty() && !starts_record(line) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Err(ParseError::new(line, "expected a record"));
+        }
+
+        pending.push_str(line);
+        pending.push('\n');
+    }
+    if !pending.is_empty() {
+        records.push(parse_record(&pending)?);
+    }
+
+    Ok(records)
+}
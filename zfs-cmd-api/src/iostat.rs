@@ -0,0 +1,293 @@
+//! Parsing the scripted (`-Hp`) output of `zpool iostat -v`
+
+use crate::ParseError;
+
+/// Which optional statistics `zpool iostat` reports
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct IostatOptions {
+    /// `-l`: average latencies
+    pub latency: bool,
+    /// `-q`: queue depths
+    pub queues: bool,
+    /// `-r`: request size histograms. Can't be combined with `latency` or `queues`.
+    pub histograms: bool,
+}
+
+impl IostatOptions {
+    pub fn args(&self) -> Result<Vec<&'static str>, &'static str> {
+        if self.histograms && (self.latency || self.queues) {
+            return Err("histograms can't be combined with latency or queue statistics");
+        }
+
+        let mut args = Vec::new();
+        if self.latency {
+            args.push("-l");
+        }
+        if self.queues {
+            args.push("-q");
+        }
+        if self.histograms {
+            args.push("-r");
+        }
+        Ok(args)
+    }
+}
+
+/// Average latencies in nanoseconds (`-l`)
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct IostatLatency {
+    pub total_read: Option<u64>,
+    pub total_write: Option<u64>,
+    pub disk_read: Option<u64>,
+    pub disk_write: Option<u64>,
+    pub sync_queue_read: Option<u64>,
+    pub sync_queue_write: Option<u64>,
+    pub async_queue_read: Option<u64>,
+    pub async_queue_write: Option<u64>,
+    pub scrub: Option<u64>,
+    pub trim: Option<u64>,
+    /// Only reported by versions supporting sequential rebuild
+    pub rebuild: Option<u64>,
+}
+
+/// Requests waiting to be issued, and issued but not complete
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct QueueDepth {
+    pub pending: Option<u64>,
+    pub active: Option<u64>,
+}
+
+/// Queue depths (`-q`)
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct IostatQueues {
+    pub sync_read: QueueDepth,
+    pub sync_write: QueueDepth,
+    pub async_read: QueueDepth,
+    pub async_write: QueueDepth,
+    pub scrub: QueueDepth,
+    pub trim: QueueDepth,
+    /// Only reported by versions supporting sequential rebuild
+    pub rebuild: Option<QueueDepth>,
+}
+
+/// Statistics of a pool or vdev over one interval. Fields which don't apply (`-` in the output),
+/// like the capacity of a mirror's children, are `None`.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct IostatStats {
+    pub alloc: Option<u64>,
+    pub free: Option<u64>,
+    /// Operations per second
+    pub read_ops: Option<u64>,
+    pub write_ops: Option<u64>,
+    /// Bytes per second
+    pub read_bytes: Option<u64>,
+    pub write_bytes: Option<u64>,
+    pub latency: Option<IostatLatency>,
+    pub queues: Option<IostatQueues>,
+}
+
+/// One row of a request size histogram (`-r`): the number of requests of `size` bytes, as
+/// individual/aggregated pairs for each queue (sync read, sync write, async read, async write,
+/// scrub, trim, and, where supported, rebuild)
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistogramBucket {
+    pub size: u64,
+    pub counts: Vec<Option<u64>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum IostatSample {
+    Stats(Box<IostatStats>),
+    Histogram(HistogramBucket),
+}
+
+/// A sample for a pool or one of its vdevs
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IostatRow {
+    /// Counts from 0. Interval 0 covers the time since the pool was imported.
+    pub interval: u64,
+    pub pool: String,
+    /// `None` for the pool as a whole
+    pub vdev: Option<String>,
+    pub sample: IostatSample,
+}
+
+/// Turns lines of `zpool iostat -Hp -v` output into `IostatRow`s
+///
+/// Scripted output doesn't indent vdevs below their pool, so the parser must be given the names
+/// of the pools reported to tell them apart.
+#[derive(Debug, Clone)]
+pub struct IostatParser {
+    pools: Vec<String>,
+    options: IostatOptions,
+    interval: u64,
+    seen: Vec<String>,
+    pool: Option<String>,
+    vdev: Option<String>,
+}
+
+fn num(field: &str, line: &str) -> Result<Option<u64>, ParseError> {
+    if field == "-" {
+        return Ok(None);
+    }
+    field
+        .parse()
+        .map(Some)
+        .map_err(|_| ParseError::new(line, "invalid number"))
+}
+
+impl IostatParser {
+    pub fn new<S: AsRef<str>>(pools: &[S], options: IostatOptions) -> Self {
+        IostatParser {
+            pools: pools.iter().map(|p| p.as_ref().to_owned()).collect(),
+            options,
+            interval: 0,
+            seen: Vec::new(),
+            pool: None,
+            vdev: None,
+        }
+    }
+
+    /// Note that `name` appears on a line, returning the pool and vdev (if any) it refers to
+    fn enter(&mut self, name: &str, line: &str) -> Result<(String, Option<String>), ParseError> {
+        if self.pools.iter().any(|p| p == name) {
+            // each interval lists every pool once
+            if self.seen.iter().any(|p| p == name) {
+                self.interval += 1;
+                self.seen.clear();
+            }
+            self.seen.push(name.to_owned());
+            self.pool = Some(name.to_owned());
+            self.vdev = None;
+        } else {
+            self.vdev = Some(name.to_owned());
+        }
+
+        let pool = self
+            .pool
+            .clone()
+            .ok_or_else(|| ParseError::new(line, "vdev before any pool"))?;
+        Ok((pool, self.vdev.clone()))
+    }
+
+    /// Parse one line of output. Returns `None` for lines which carry no sample (blank lines
+    /// separating allocation classes, and histogram headers).
+    pub fn parse_line(&mut self, line: &str) -> Result<Option<IostatRow>, ParseError> {
+        let fields: Vec<&str> = line.split('\t').map(|f| f.trim()).collect();
+        if fields.iter().all(|f| f.is_empty()) {
+            return Ok(None);
+        }
+
+        if self.options.histograms {
+            return self.parse_histogram_line(line, &fields);
+        }
+
+        let (pool, vdev) = self.enter(fields[0], line)?;
+        let stats = self.parse_stats(line, &fields[1..])?;
+        Ok(Some(IostatRow {
+            interval: self.interval,
+            pool,
+            vdev,
+            sample: IostatSample::Stats(Box::new(stats)),
+        }))
+    }
+
+    fn parse_histogram_line(
+        &mut self,
+        line: &str,
+        fields: &[&str],
+    ) -> Result<Option<IostatRow>, ParseError> {
+        // a pool or vdev name, followed by its buckets
+        let size = match fields[0].parse() {
+            Ok(size) => size,
+            Err(_) => {
+                self.enter(fields[0], line)?;
+                return Ok(None);
+            }
+        };
+
+        let counts = fields[1..]
+            .iter()
+            .map(|f| num(f, line))
+            .collect::<Result<Vec<_>, _>>()?;
+        let pool = self
+            .pool
+            .clone()
+            .ok_or_else(|| ParseError::new(line, "histogram before any pool"))?;
+        Ok(Some(IostatRow {
+            interval: self.interval,
+            pool,
+            vdev: self.vdev.clone(),
+            sample: IostatSample::Histogram(HistogramBucket { size, counts }),
+        }))
+    }
+
+    fn parse_stats(&self, line: &str, fields: &[&str]) -> Result<IostatStats, ParseError> {
+        let v = fields
+            .iter()
+            .map(|f| num(f, line))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // versions with sequential rebuild add a latency column and a pair of queue columns
+        let (latency_len, queue_len) = match (self.options.latency, self.options.queues) {
+            (true, true) if v.len() == 6 + 11 + 14 => (11, 14),
+            (true, true) => (10, 12),
+            (true, false) => (v.len().saturating_sub(6), 0),
+            (false, true) => (0, v.len().saturating_sub(6)),
+            (false, false) => (0, 0),
+        };
+        if v.len() != 6 + latency_len + queue_len
+            || (self.options.latency && !(10..=11).contains(&latency_len))
+            || (self.options.queues && queue_len != 12 && queue_len != 14)
+        {
+            return Err(ParseError::new(line, "unexpected number of columns"));
+        }
+
+        let mut stats = IostatStats {
+            alloc: v[0],
+            free: v[1],
+            read_ops: v[2],
+            write_ops: v[3],
+            read_bytes: v[4],
+            write_bytes: v[5],
+            latency: None,
+            queues: None,
+        };
+
+        if self.options.latency {
+            let l = &v[6..6 + latency_len];
+            stats.latency = Some(IostatLatency {
+                total_read: l[0],
+                total_write: l[1],
+                disk_read: l[2],
+                disk_write: l[3],
+                sync_queue_read: l[4],
+                sync_queue_write: l[5],
+                async_queue_read: l[6],
+                async_queue_write: l[7],
+                scrub: l[8],
+                trim: l[9],
+                rebuild: l.get(10).copied().flatten(),
+            });
+        }
+
+        if self.options.queues {
+            let q = &v[6 + latency_len..];
+            let depth = |i: usize| QueueDepth {
+                pending: q[i * 2],
+                active: q[i * 2 + 1],
+            };
+            stats.queues = Some(IostatQueues {
+                sync_read: depth(0),
+                sync_write: depth(1),
+                async_read: depth(2),
+                async_write: depth(3),
+                scrub: depth(4),
+                trim: depth(5),
+                rebuild: if q.len() == 14 { Some(depth(6)) } else { None },
+            });
+        }
+
+        Ok(stats)
+    }
+}
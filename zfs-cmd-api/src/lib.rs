@@ -1,13 +1,32 @@
 use core::fmt;
 
-//pub mod zfs;
+pub mod allow;
+pub mod audit;
+pub mod destroy;
+pub mod diff;
+pub mod encryption;
+pub mod events;
+pub mod history;
+pub mod iostat;
+pub mod maintenance;
+pub mod mount;
+pub mod program;
+pub mod space;
+pub mod status;
+pub mod userspace;
+pub mod value;
+pub mod vdev;
+pub mod wait;
+pub mod zfs;
 pub mod zpool;
 
+pub use zfs::*;
+
 pub async fn pools() -> Result<Vec<PoolName>, Error> {
     zpool::ZpoolCmd::default().list_pools().await
 }
 
-pub async fn pool_list(pool: &str) -> Result<zpool::Zpool, Error> {
+pub async fn pool_list(pool: &str) -> Result<PoolName, Error> {
     zpool::ZpoolCmd::default().list_pool(pool).await
 }
 
@@ -49,3 +68,27 @@ impl fmt::Debug for Error {
 }
 
 impl std::error::Error for Error {}
+
+/// A line of command output which could not be understood
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError {
+    line: String,
+    msg: &'static str,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: &str, msg: &'static str) -> Self {
+        ParseError {
+            line: line.to_owned(),
+            msg,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {:?}", self.msg, self.line)
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,155 @@
+//! Controlling scrubs, TRIMs, and initialization of a pool, and deciding when a pool is due for
+//! another scrub
+
+use crate::status::{PoolStatus, ScanFunction, ScanState};
+use std::time::{Duration, SystemTime};
+
+/// What `zpool scrub` should do
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScrubAction {
+    /// Start a scrub, or resume a paused one
+    Start,
+    /// `-p`: pause a running scrub. Its progress is kept.
+    Pause,
+    /// `-s`: stop a running scrub
+    Stop,
+}
+
+impl ScrubAction {
+    /// Arguments selecting this action. `error_scrub` (`-e`) limits the scrub to blocks with
+    /// known errors.
+    pub fn args(&self, error_scrub: bool) -> Vec<&'static str> {
+        let mut args = Vec::new();
+        if error_scrub {
+            args.push("-e");
+        }
+        match self {
+            ScrubAction::Start => {}
+            ScrubAction::Pause => args.push("-p"),
+            ScrubAction::Stop => args.push("-s"),
+        }
+        args
+    }
+}
+
+/// What `zpool trim` should do
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TrimAction {
+    /// Start trimming (or resume a suspended trim)
+    Start {
+        /// `-d`: securely discard, failing if a device doesn't support it
+        secure: bool,
+        /// `-r`: bytes per second to trim each device at
+        rate: Option<u64>,
+    },
+    /// `-c`: cancel trimming. Progress is lost.
+    Cancel,
+    /// `-s`: suspend trimming. It can be resumed with `Start`.
+    Suspend,
+}
+
+impl TrimAction {
+    pub fn args(&self) -> Vec<String> {
+        match *self {
+            TrimAction::Start { secure, rate } => {
+                let mut args = Vec::new();
+                if secure {
+                    args.push("-d".to_owned());
+                }
+                if let Some(rate) = rate {
+                    args.push("-r".to_owned());
+                    args.push(rate.to_string());
+                }
+                args
+            }
+            TrimAction::Cancel => vec!["-c".to_owned()],
+            TrimAction::Suspend => vec!["-s".to_owned()],
+        }
+    }
+}
+
+/// What `zpool initialize` should do
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InitializeAction {
+    /// Start writing to all unallocated space (or resume a suspended initialize)
+    Start,
+    /// `-c`: cancel initializing
+    Cancel,
+    /// `-s`: suspend initializing. It can be resumed with `Start`.
+    Suspend,
+    /// `-u`: clear the initialized state, so devices can be initialized again
+    Uninit,
+}
+
+impl InitializeAction {
+    pub fn args(&self) -> Vec<&'static str> {
+        match self {
+            InitializeAction::Start => vec![],
+            InitializeAction::Cancel => vec!["-c"],
+            InitializeAction::Suspend => vec!["-s"],
+            InitializeAction::Uninit => vec!["-u"],
+        }
+    }
+}
+
+/// What `zpool checkpoint` should do
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CheckpointAction {
+    /// Checkpoint the pool's current state. A pool has at most one checkpoint.
+    Create,
+    /// `-d`: discard the checkpoint, freeing the space it holds
+    Discard {
+        /// `-w`: return only once the space is freed
+        wait: bool,
+    },
+}
+
+impl CheckpointAction {
+    pub fn args(&self) -> Vec<&'static str> {
+        match *self {
+            CheckpointAction::Create => vec![],
+            CheckpointAction::Discard { wait: false } => vec!["-d"],
+            CheckpointAction::Discard { wait: true } => vec!["-d", "-w"],
+        }
+    }
+}
+
+/// Whether a pool should be scrubbed now, as decided by `scrub_due()`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ScrubDecision {
+    /// No scan has completed within the interval
+    Due,
+    /// The last scan completed recently. Another is due at the given time.
+    NotDue(SystemTime),
+    /// A scan is running (or paused)
+    InProgress(ScanFunction),
+}
+
+/// Decide if the pool described by `status` should be scrubbed at `now`, given scrubs should be
+/// at least `min_interval` apart.
+///
+/// Both scrubs and resilvers count as a completed scan, as `zpool status` only reports the most
+/// recent one. A canceled scan, or one without a known end time, is treated as if no scan had
+/// completed.
+pub fn scrub_due(status: &PoolStatus, now: SystemTime, min_interval: Duration) -> ScrubDecision {
+    let scan = match status.scan {
+        Some(ref scan) => scan,
+        None => return ScrubDecision::Due,
+    };
+
+    match scan.state {
+        ScanState::Scanning => return ScrubDecision::InProgress(scan.function.clone()),
+        ScanState::Finished => {}
+        ScanState::Canceled | ScanState::Other(_) => return ScrubDecision::Due,
+    }
+
+    match scan.function {
+        ScanFunction::Scrub | ScanFunction::Resilver => {}
+        ScanFunction::ErrorScrub | ScanFunction::Other(_) => return ScrubDecision::Due,
+    }
+
+    match scan.end_time {
+        Some(end) if end + min_interval > now => ScrubDecision::NotDue(end + min_interval),
+        _ => ScrubDecision::Due,
+    }
+}
@@ -0,0 +1,110 @@
+//! Mount state of datasets and flags for `zfs mount` and `zfs unmount`
+
+use crate::ParseError;
+use enumflags2::bitflags;
+
+/// `canmount` property
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CanMount {
+    On,
+    Off,
+    NoAuto,
+}
+
+/// `mountpoint` property
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Mountpoint {
+    Path(String),
+    /// Mounted with mount(8), not `zfs mount`
+    Legacy,
+    /// Not mountable
+    None,
+}
+
+/// Mount related properties of a single filesystem
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MountInfo {
+    pub name: String,
+    pub mounted: bool,
+    /// `None` for datasets which can't be mounted (volumes, snapshots)
+    pub mountpoint: Option<Mountpoint>,
+    /// `None` for datasets which can't be mounted (volumes, snapshots)
+    pub canmount: Option<CanMount>,
+}
+
+/// The properties to request from `zfs list` in the order `MountInfo::from_list_row` expects
+pub const LIST_ELEMENTS: &[&str] = &["name", "mounted", "mountpoint", "canmount"];
+
+impl MountInfo {
+    /// Parse a row of `zfs list -pH -o <LIST_ELEMENTS>`
+    pub fn from_list_row(row: &[String]) -> Result<Self, ParseError> {
+        let err = |msg| ParseError::new(&row.join("\t"), msg);
+        if row.len() != LIST_ELEMENTS.len() {
+            return Err(err("unexpected number of columns"));
+        }
+
+        let mounted = match row[1].as_str() {
+            "yes" => true,
+            "no" | "-" => false,
+            _ => return Err(err("unknown mounted")),
+        };
+
+        let mountpoint = match row[2].as_str() {
+            "-" => None,
+            "none" => Some(Mountpoint::None),
+            "legacy" => Some(Mountpoint::Legacy),
+            p => Some(Mountpoint::Path(p.to_owned())),
+        };
+
+        let canmount = match row[3].as_str() {
+            "-" => None,
+            "on" => Some(CanMount::On),
+            "off" => Some(CanMount::Off),
+            "noauto" => Some(CanMount::NoAuto),
+            _ => return Err(err("unknown canmount")),
+        };
+
+        Ok(MountInfo {
+            name: row[0].clone(),
+            mounted,
+            mountpoint,
+            canmount,
+        })
+    }
+}
+
+#[bitflags]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MountFlags {
+    /// -O: mount over a non-empty directory
+    Overlay = 1 << 0,
+    /// -l: load keys for encrypted datasets as needed
+    LoadKeys = 1 << 1,
+    /// -f: force mount even if the dataset would not normally be mounted (ie: `canmount=off`)
+    Force = 1 << 2,
+}
+
+#[bitflags]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnmountFlags {
+    /// -f: unmount even if in use
+    Force = 1 << 0,
+    /// -u: unload keys of encryption roots once unmounted
+    UnloadKeys = 1 << 1,
+}
+
+/// Extract the mountpoint (or dataset) and reason from a `cannot unmount` line of `zfs unmount`
+/// (or `zfs recv`) stderr
+///
+/// ```text
+/// cannot unmount '/tank/home': pool or dataset is busy
+/// ```
+pub fn parse_unmount_error(stderr: &str) -> Option<(String, String)> {
+    stderr.lines().find_map(|line| {
+        let rest = line.strip_prefix("cannot unmount '")?;
+        let (target, reason) = rest.split_once("': ")?;
+        Some((target.to_owned(), reason.to_owned()))
+    })
+}
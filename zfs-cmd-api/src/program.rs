@@ -0,0 +1,43 @@
+//! Running ZFS channel programs (`zfs program`)
+
+use crate::ParseError;
+
+/// Options for `Zfs::program()`
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ProgramOptions {
+    /// `-n`: run in read-only mode, where the program can't make changes
+    pub read_only: bool,
+    /// `-t`: limit on the number of Lua instructions executed
+    pub instruction_limit: Option<u64>,
+    /// `-m`: limit on the memory the program may use, in bytes
+    pub memory_limit: Option<u64>,
+}
+
+/// Extract the value returned by a channel program from the output of `zfs program -j`
+///
+/// ```text
+/// {"return": {"destroyed": ["tank/home@a"]}}
+/// ```
+pub fn parse_output(output: &str) -> Result<serde_json::Value, ParseError> {
+    let err = |msg| ParseError::new(output, msg);
+    let v: serde_json::Value = serde_json::from_str(output).map_err(|_| err("invalid json"))?;
+
+    match v {
+        // programs which don't return anything produce `{}`
+        serde_json::Value::Object(mut o) => {
+            Ok(o.remove("return").unwrap_or(serde_json::Value::Null))
+        }
+        _ => Err(err("not an object")),
+    }
+}
+
+/// Extract the error message from the stderr of a failed `zfs program`
+///
+/// ```text
+/// Channel program execution failed:
+/// [string "channel program"]:1: attempt to call a nil value (field 'foo')
+/// ```
+pub fn parse_error(stderr: &str) -> Option<String> {
+    let msg = stderr.strip_prefix("Channel program execution failed:\n")?;
+    Some(msg.trim_end().to_owned())
+}
@@ -0,0 +1,101 @@
+//! Space accounting: the `used*` properties and `zfs destroy` reclaim estimates
+
+use crate::ParseError;
+
+/// Space accounting properties of a single dataset, in bytes
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SpaceUsage {
+    pub name: String,
+    pub used: u64,
+    pub available: Option<u64>,
+    pub referenced: u64,
+    pub used_by_snapshots: Option<u64>,
+    pub used_by_dataset: Option<u64>,
+    pub used_by_children: Option<u64>,
+    pub used_by_refreservation: Option<u64>,
+    /// Space written since the previous snapshot
+    pub written: Option<u64>,
+}
+
+/// The properties to request from `zfs list` in the order `SpaceUsage::from_list_row` expects
+pub const LIST_ELEMENTS: &[&str] = &[
+    "name",
+    "used",
+    "available",
+    "referenced",
+    "usedbysnapshots",
+    "usedbydataset",
+    "usedbychildren",
+    "usedbyrefreservation",
+    "written",
+];
+
+/// Parse a parsable (`-p`) numeric property value, where `-` indicates it does not apply
+pub fn parse_bytes(v: &str) -> Result<Option<u64>, &'static str> {
+    if v == "-" {
+        return Ok(None);
+    }
+    v.parse().map(Some).map_err(|_| "invalid number")
+}
+
+impl SpaceUsage {
+    /// Parse a row of `zfs list -pH -o <LIST_ELEMENTS>`
+    pub fn from_list_row(row: &[String]) -> Result<Self, ParseError> {
+        let line = row.join("\t");
+        let err = |msg| ParseError::new(&line, msg);
+        if row.len() != LIST_ELEMENTS.len() {
+            return Err(err("unexpected number of columns"));
+        }
+
+        let bytes = |i: usize| parse_bytes(&row[i]).map_err(err);
+
+        Ok(SpaceUsage {
+            name: row[0].clone(),
+            used: bytes(1)?.ok_or_else(|| err("missing used"))?,
+            available: bytes(2)?,
+            referenced: bytes(3)?.ok_or_else(|| err("missing referenced"))?,
+            used_by_snapshots: bytes(4)?,
+            used_by_dataset: bytes(5)?,
+            used_by_children: bytes(6)?,
+            used_by_refreservation: bytes(7)?,
+            written: bytes(8)?,
+        })
+    }
+}
+
+/// Result of a dry run destroy (`zfs destroy -nvp`)
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct DestroyEstimate {
+    /// Datasets that would be destroyed
+    pub destroyed: Vec<String>,
+    /// Bytes that would be freed
+    pub reclaim: u64,
+}
+
+impl DestroyEstimate {
+    /// Parse the output of `zfs destroy -nvp`
+    ///
+    /// ```text
+    /// destroy    tank/home@a
+    /// destroy    tank/home@b
+    /// reclaim    1048576
+    /// ```
+    pub fn parse(output: &str) -> Result<Self, ParseError> {
+        let mut estimate = DestroyEstimate::default();
+
+        for line in output.lines() {
+            let err = |msg| ParseError::new(line, msg);
+            match line.split_once('\t') {
+                Some(("destroy", ds)) => estimate.destroyed.push(ds.to_owned()),
+                // only emitted when snapshots are destroyed
+                Some(("reclaim", v)) => {
+                    estimate.reclaim = v.parse().map_err(|_| err("invalid reclaim"))?;
+                }
+                _ if line.is_empty() => {}
+                _ => return Err(err("unknown line")),
+            }
+        }
+
+        Ok(estimate)
+    }
+}
@@ -0,0 +1,736 @@
+//! Pool health as reported by `zpool status`
+//!
+//! `zpool status -j` (OpenZFS 2.3 and later) is preferred. Older versions only produce text,
+//! which `parse_text` understands well enough to extract the same information. Scan start and end
+//! times are printed in local time, so run `zpool status` with `TZ=UTC` for them to be read
+//! correctly.
+//!
+//! `zpool import` describes pools available for import in the same format, which `parse_import`
+//! understands.
+
+use crate::history::days_from_civil;
+use crate::value::{parse_percent, parse_size};
+use crate::ParseError;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// State of a pool or vdev
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum VdevState {
+    Online,
+    Degraded,
+    Faulted,
+    Offline,
+    Unavail,
+    Removed,
+    /// A spare which is available for use
+    Avail,
+    /// A spare which is in use
+    Inuse,
+    Other(String),
+}
+
+impl VdevState {
+    pub fn parse(v: &str) -> Self {
+        match v {
+            "ONLINE" => VdevState::Online,
+            "DEGRADED" => VdevState::Degraded,
+            "FAULTED" => VdevState::Faulted,
+            "OFFLINE" => VdevState::Offline,
+            "UNAVAIL" => VdevState::Unavail,
+            "REMOVED" => VdevState::Removed,
+            "AVAIL" => VdevState::Avail,
+            "INUSE" => VdevState::Inuse,
+            _ => VdevState::Other(v.to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ScanFunction {
+    Scrub,
+    Resilver,
+    /// `zpool scrub -e`: scrub only blocks with known errors
+    ErrorScrub,
+    Other(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ScanState {
+    /// In progress (or paused)
+    Scanning,
+    Finished,
+    Canceled,
+    Other(String),
+}
+
+/// The most recent (or current) scrub or resilver
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScanStatus {
+    pub function: ScanFunction,
+    pub state: ScanState,
+    /// Text output prints this in local time, so it is only correct if `zpool status` ran with
+    /// `TZ=UTC`
+    pub start_time: Option<SystemTime>,
+    /// Only once finished or canceled. Like `start_time`, needs `TZ=UTC` for text output.
+    pub end_time: Option<SystemTime>,
+    /// Bytes to be scanned
+    pub to_examine: Option<u64>,
+    /// Bytes scanned (metadata read) so far
+    pub examined: Option<u64>,
+    /// Bytes issued for verification so far
+    pub issued: Option<u64>,
+    /// Errors found so far
+    pub errors: Option<u64>,
+    /// Percentage of `to_examine` issued, while scanning
+    pub percent_done: Option<f64>,
+    /// Estimated time remaining, while scanning
+    pub eta: Option<Duration>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct VdevStatus {
+    pub name: String,
+    /// `root`, `mirror`, `disk`, etc. Only available from `zpool status -j`.
+    pub vdev_type: Option<String>,
+    /// Device path of leaf vdevs. Only available from `zpool status -j`.
+    pub path: Option<String>,
+    pub state: VdevState,
+    pub read_errors: u64,
+    pub write_errors: u64,
+    pub checksum_errors: u64,
+    pub children: Vec<VdevStatus>,
+}
+
+impl VdevStatus {
+    /// This vdev and all of its descendants, parents before their children
+    pub fn walk(&self) -> Vec<&VdevStatus> {
+        let mut v = vec![self];
+        for child in self.children.iter() {
+            v.extend(child.walk());
+        }
+        v
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PoolStatus {
+    pub name: String,
+    pub state: VdevState,
+    /// Description of any problem with the pool
+    pub status: Option<String>,
+    /// Recommended action to resolve `status`
+    pub action: Option<String>,
+    pub scan: Option<ScanStatus>,
+    /// The root vdev (named for the pool) and its descendants
+    pub root: Option<VdevStatus>,
+    pub logs: Vec<VdevStatus>,
+    pub cache: Vec<VdevStatus>,
+    pub spares: Vec<VdevStatus>,
+    /// Number of data errors, if known
+    pub error_count: Option<u64>,
+    /// Files (or `dataset:<0xobject>` when the file can't be named) with permanent errors.
+    /// Only listed with `zpool status -v`.
+    pub errors: Vec<String>,
+}
+
+/// A number which `zpool status -j` may give as a string (the default) or an integer (with
+/// `--json-int`)
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JsonNum {
+    Str(String),
+    Int(u64),
+}
+
+impl JsonNum {
+    fn to_u64(&self) -> Option<u64> {
+        match self {
+            JsonNum::Str(s) => parse_size(s).ok().flatten(),
+            JsonNum::Int(i) => Some(*i),
+        }
+    }
+}
+
+fn num(v: &Option<JsonNum>) -> Option<u64> {
+    v.as_ref().and_then(|v| v.to_u64())
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonStatus {
+    pools: BTreeMap<String, JsonPool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonPool {
+    name: String,
+    state: String,
+    status: Option<String>,
+    action: Option<String>,
+    scan_stats: Option<JsonScan>,
+    #[serde(default)]
+    vdevs: BTreeMap<String, JsonVdev>,
+    #[serde(default)]
+    special: BTreeMap<String, JsonVdev>,
+    #[serde(default)]
+    dedup: BTreeMap<String, JsonVdev>,
+    #[serde(default)]
+    logs: BTreeMap<String, JsonVdev>,
+    #[serde(default)]
+    l2cache: BTreeMap<String, JsonVdev>,
+    #[serde(default)]
+    spares: BTreeMap<String, JsonVdev>,
+    error_count: Option<JsonNum>,
+    errors: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonVdev {
+    name: String,
+    vdev_type: Option<String>,
+    path: Option<String>,
+    state: String,
+    read_errors: Option<JsonNum>,
+    write_errors: Option<JsonNum>,
+    checksum_errors: Option<JsonNum>,
+    #[serde(default)]
+    vdevs: BTreeMap<String, JsonVdev>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonScan {
+    function: String,
+    state: String,
+    start_time: Option<JsonNum>,
+    end_time: Option<JsonNum>,
+    to_examine: Option<JsonNum>,
+    examined: Option<JsonNum>,
+    issued: Option<JsonNum>,
+    errors: Option<JsonNum>,
+    pass_start: Option<JsonNum>,
+    issued_bytes_per_scan: Option<JsonNum>,
+}
+
+impl From<JsonVdev> for VdevStatus {
+    fn from(v: JsonVdev) -> Self {
+        VdevStatus {
+            state: VdevState::parse(&v.state),
+            read_errors: num(&v.read_errors).unwrap_or(0),
+            write_errors: num(&v.write_errors).unwrap_or(0),
+            checksum_errors: num(&v.checksum_errors).unwrap_or(0),
+            children: v.vdevs.into_values().map(From::from).collect(),
+            name: v.name,
+            vdev_type: v.vdev_type,
+            path: v.path,
+        }
+    }
+}
+
+fn epoch(secs: Option<u64>) -> Option<SystemTime> {
+    secs.filter(|s| *s != 0)
+        .and_then(|s| UNIX_EPOCH.checked_add(Duration::from_secs(s)))
+}
+
+impl JsonScan {
+    fn into_scan(self, now: SystemTime) -> Option<ScanStatus> {
+        let function = match self.function.as_str() {
+            "SCRUB" => ScanFunction::Scrub,
+            "RESILVER" => ScanFunction::Resilver,
+            "ERRORSCRUB" => ScanFunction::ErrorScrub,
+            _ => ScanFunction::Other(self.function.clone()),
+        };
+        let state = match self.state.as_str() {
+            "NONE" => return None,
+            "SCANNING" | "ERRORSCRUBBING" => ScanState::Scanning,
+            "FINISHED" | "ERRORSCRUBBED" => ScanState::Finished,
+            "CANCELED" => ScanState::Canceled,
+            _ => ScanState::Other(self.state.clone()),
+        };
+
+        let to_examine = num(&self.to_examine);
+        let issued = num(&self.issued);
+
+        let (mut percent_done, mut eta) = (None, None);
+        if state == ScanState::Scanning {
+            if let (Some(total), Some(issued)) = (to_examine, issued) {
+                if total > 0 {
+                    percent_done = Some(issued as f64 * 100.0 / total as f64);
+                }
+
+                // rate over the current pass, as `zpool status` computes it
+                let pass_issued = num(&self.issued_bytes_per_scan);
+                let elapsed = epoch(num(&self.pass_start))
+                    .and_then(|start| now.duration_since(start).ok())
+                    .map(|d| d.as_secs());
+                if let (Some(pass_issued), Some(elapsed)) = (pass_issued, elapsed) {
+                    if let Some(rate) = pass_issued.checked_div(elapsed).filter(|&r| r > 0) {
+                        eta = Some(Duration::from_secs(total.saturating_sub(issued) / rate));
+                    }
+                }
+            }
+        }
+
+        Some(ScanStatus {
+            function,
+            state,
+            start_time: epoch(num(&self.start_time)),
+            end_time: epoch(num(&self.end_time)),
+            to_examine,
+            examined: num(&self.examined),
+            issued,
+            errors: num(&self.errors),
+            percent_done,
+            eta,
+        })
+    }
+}
+
+/// Extract file names from the `errors` of a pool in `zpool status -jv`, which may be either a
+/// list or an object keyed by file name.
+fn json_errors(v: serde_json::Value) -> Vec<String> {
+    fn name(key: Option<&str>, v: &serde_json::Value) -> Option<String> {
+        match v {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Object(o) => o
+                .get("path")
+                .and_then(|p| p.as_str())
+                .or(key)
+                .map(|s| s.to_owned()),
+            _ => key.map(|s| s.to_owned()),
+        }
+    }
+
+    match v {
+        serde_json::Value::Array(a) => a.iter().filter_map(|v| name(None, v)).collect(),
+        serde_json::Value::Object(o) => o.iter().filter_map(|(k, v)| name(Some(k), v)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Parse the output of `zpool status -jpv`. `now` is used to estimate the time remaining for
+/// scans in progress.
+pub fn parse_json(output: &[u8], now: SystemTime) -> Result<Vec<PoolStatus>, ParseError> {
+    let status: JsonStatus = serde_json::from_slice(output)
+        .map_err(|_| ParseError::new(&String::from_utf8_lossy(output), "invalid json"))?;
+
+    Ok(status
+        .pools
+        .into_values()
+        .map(|p| {
+            let list = |m: BTreeMap<String, JsonVdev>| -> Vec<VdevStatus> {
+                m.into_values().map(From::from).collect()
+            };
+
+            // `special` and `dedup` class vdevs belong to the root vdev, as in the text output
+            let mut root = p.vdevs.into_values().next().map(VdevStatus::from);
+            if let Some(ref mut root) = root {
+                root.children.extend(list(p.special));
+                root.children.extend(list(p.dedup));
+            }
+
+            PoolStatus {
+                name: p.name,
+                state: VdevState::parse(&p.state),
+                status: p.status,
+                action: p.action,
+                scan: p.scan_stats.and_then(|s| s.into_scan(now)),
+                root,
+                logs: list(p.logs),
+                cache: list(p.l2cache),
+                spares: list(p.spares),
+                error_count: num(&p.error_count),
+                errors: p.errors.map(json_errors).unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+/// Parse the `scan:` text of `zpool status`, ie:
+///
+/// ```text
+/// scrub in progress since Sun Oct  1 00:24:01 2023
+///     1.23T / 1.75T scanned at 500M/s, 1.00T / 1.75T issued at 400M/s
+///     0B repaired, 57.14% done, 00:30:00 to go
+/// ```
+pub fn parse_scan_text(text: &str) -> Option<ScanStatus> {
+    let text = text.trim();
+    if text.is_empty() || text.starts_with("none requested") {
+        return None;
+    }
+
+    let function = if text.starts_with("scrub") {
+        ScanFunction::Scrub
+    } else if text.starts_with("resilver") {
+        ScanFunction::Resilver
+    } else if text.starts_with("error scrub") {
+        ScanFunction::ErrorScrub
+    } else {
+        ScanFunction::Other(text.split_whitespace().next().unwrap_or("").to_owned())
+    };
+
+    let first = text.lines().next().unwrap_or("");
+    let state = if first.contains("in progress") || first.contains("paused") {
+        ScanState::Scanning
+    } else if first.contains("canceled") {
+        ScanState::Canceled
+    } else if first.contains(" with ") {
+        ScanState::Finished
+    } else {
+        ScanState::Other(first.to_owned())
+    };
+
+    let mut scan = ScanStatus {
+        function,
+        state,
+        start_time: None,
+        end_time: None,
+        to_examine: None,
+        examined: None,
+        issued: None,
+        errors: None,
+        percent_done: None,
+        eta: None,
+    };
+
+    let words: Vec<&str> = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|w| !w.is_empty())
+        .collect();
+    let at = |i: usize| words.get(i).copied().unwrap_or("");
+    let size = |w: &str| parse_size(w).ok().flatten();
+
+    for i in 0..words.len() {
+        match (at(i + 1), at(i + 3)) {
+            ("/", "scanned") => {
+                scan.examined = size(at(i));
+                scan.to_examine = size(at(i + 2));
+            }
+            ("/", "issued") => {
+                scan.issued = size(at(i));
+                scan.to_examine = scan.to_examine.or_else(|| size(at(i + 2)));
+            }
+            ("scanned", _) if scan.examined.is_none() => scan.examined = size(at(i)),
+            ("issued", _) if scan.issued.is_none() => scan.issued = size(at(i)),
+            ("total", _) => scan.to_examine = size(at(i)),
+            ("done", _) if at(i).ends_with('%') => {
+                scan.percent_done = parse_percent(at(i)).ok().flatten();
+            }
+            ("to", _) if at(i + 2) == "go" => {
+                let mut secs = parse_hms(at(i));
+                if at(i.wrapping_sub(1)) == "days" {
+                    let days: Option<u64> = at(i.wrapping_sub(2)).parse().ok();
+                    secs = secs.and_then(|s| Some(s + days? * 24 * 60 * 60));
+                }
+                scan.eta = secs.map(Duration::from_secs);
+            }
+            _ => {}
+        }
+
+        if at(i) == "with" && at(i + 2) == "errors" {
+            scan.errors = at(i + 1).parse().ok();
+        }
+
+        // `since Sun Oct  1 00:24:01 2023` starts an in progress scan, `on ...` ends others
+        match at(i) {
+            "since" => scan.start_time = scan.start_time.or(parse_ctime(&words[i + 1..])),
+            "on" => scan.end_time = scan.end_time.or(parse_ctime(&words[i + 1..])),
+            _ => {}
+        }
+    }
+
+    Some(scan)
+}
+
+/// Parse a UTC time printed by `ctime()` (`Sun Oct  8 00:24:05 2023`), split into words
+fn parse_ctime(words: &[&str]) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let (month, day, time, year) = match *words {
+        [_, month, day, time, year, ..] => (month, day, time, year),
+        _ => return None,
+    };
+    let month = MONTHS.iter().position(|m| *m == month)? as u32 + 1;
+    let day: u32 = day.parse().ok()?;
+    let year: i64 = year.parse().ok()?;
+    let secs = parse_hms(time)?;
+    if !(1..=31).contains(&day) || secs >= 24 * 60 * 60 {
+        return None;
+    }
+
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 24 * 60 * 60 + secs))
+}
+
+fn parse_hms(v: &str) -> Option<u64> {
+    let mut secs = 0;
+    let mut n = 0;
+    for part in v.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+        n += 1;
+    }
+    if n == 3 {
+        Some(secs)
+    } else {
+        None
+    }
+}
+
+/// Arrange `(depth, vdev)` pairs, listed parents first as in the `config:` section, into trees
+fn build_tree(
+    items: &mut std::iter::Peekable<std::vec::IntoIter<(usize, VdevStatus)>>,
+    depth: usize,
+) -> Vec<VdevStatus> {
+    let mut out = Vec::new();
+    while let Some((d, _)) = items.peek() {
+        if *d < depth {
+            break;
+        }
+        let (d, mut vdev) = items.next().unwrap();
+        vdev.children = build_tree(items, d + 1);
+        out.push(vdev);
+    }
+    out
+}
+
+#[derive(Default)]
+struct TextPool {
+    name: String,
+    fields: BTreeMap<String, String>,
+    config: Vec<String>,
+    errors: Vec<String>,
+}
+
+impl TextPool {
+    /// `import` selects the `zpool import` flavor of the `config:` section, which has no header
+    /// line or error counts.
+    fn into_status(self, import: bool) -> Result<PoolStatus, ParseError> {
+        let field = |k: &str| self.fields.get(k).map(|v| v.trim().to_owned());
+        let state = field("state").ok_or_else(|| ParseError::new(&self.name, "missing state"))?;
+
+        // sections: "" for the pool's own tree, then `logs`, `cache`, `spares`, etc
+        let mut sections: BTreeMap<String, Vec<(usize, VdevStatus)>> = BTreeMap::new();
+        let mut section = String::new();
+        let skip = if import { 0 } else { 1 };
+        for line in self.config.iter().skip(skip) {
+            let body = line.trim_start_matches('\t');
+            let indent = body.len() - body.trim_start_matches(' ').len();
+            let fields: Vec<&str> = body.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+
+            if indent == 0 && fields.len() == 1 {
+                section = fields[0].to_owned();
+                continue;
+            }
+
+            let err = |msg| ParseError::new(line, msg);
+            let count = |i: usize| -> Result<u64, ParseError> {
+                match fields.get(i) {
+                    None => Ok(0),
+                    Some(_) if import => Ok(0),
+                    Some(v) => parse_size(v)
+                        .map_err(|_| err("invalid error count"))
+                        .map(|v| v.unwrap_or(0)),
+                }
+            };
+            // vdevs in sections are indented one level beneath the section name
+            let depth = if section.is_empty() {
+                indent / 2
+            } else {
+                (indent / 2).saturating_sub(1)
+            };
+            let vdev = VdevStatus {
+                name: fields[0].to_owned(),
+                vdev_type: None,
+                path: None,
+                state: VdevState::parse(fields.get(1).copied().unwrap_or("")),
+                read_errors: count(2)?,
+                write_errors: count(3)?,
+                checksum_errors: count(4)?,
+                children: Vec::new(),
+            };
+            sections
+                .entry(section.clone())
+                .or_default()
+                .push((depth, vdev));
+        }
+
+        let mut tree = |name: &str| -> Vec<VdevStatus> {
+            let items = sections.remove(name).unwrap_or_default();
+            build_tree(&mut items.into_iter().peekable(), 0)
+        };
+
+        let mut root = tree("").into_iter().next();
+        let logs = tree("logs");
+        let cache = tree("cache");
+        let spares = tree("spares");
+        // `special` and `dedup` class vdevs belong to the root vdev
+        for (_, items) in sections.into_iter() {
+            let vdevs = build_tree(&mut items.into_iter().peekable(), 0);
+            if let Some(ref mut root) = root {
+                root.children.extend(vdevs);
+            }
+        }
+
+        let errors_line = field("errors").unwrap_or_default();
+        let error_count = if errors_line.starts_with("No known data errors") {
+            Some(0)
+        } else {
+            errors_line
+                .split_whitespace()
+                .next()
+                .and_then(|n| n.parse().ok())
+        };
+
+        Ok(PoolStatus {
+            state: VdevState::parse(&state),
+            status: field("status"),
+            action: field("action"),
+            scan: field("scan").and_then(|s| parse_scan_text(&s)),
+            root,
+            logs,
+            cache,
+            spares,
+            error_count,
+            errors: self.errors,
+            name: self.name,
+        })
+    }
+}
+
+const TEXT_KEYS: &[&str] = &[
+    "pool",
+    "id",
+    "state",
+    "status",
+    "action",
+    "see",
+    "scan",
+    "remove",
+    "checkpoint",
+    "config",
+    "errors",
+];
+
+/// Parse the text output of `zpool status -pv`
+pub fn parse_text(output: &str) -> Result<Vec<PoolStatus>, ParseError> {
+    text_pools(output)?
+        .into_iter()
+        .map(|p| p.into_status(false))
+        .collect()
+}
+
+/// A pool `zpool import` found
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImportablePool {
+    /// The pool's guid, which can be used to import it in place of its name
+    pub id: u64,
+    /// Whether the pool was destroyed (only listed with `zpool import -D`)
+    pub destroyed: bool,
+    /// The pool's state, status, action, and config. Error counts are always 0, and `scan` and
+    /// `errors` are not reported.
+    pub pool: PoolStatus,
+}
+
+/// Parse the output of `zpool import` when listing pools available for import
+///
+/// ```text
+///    pool: tank
+///      id: 6904587473384093231
+///   state: ONLINE
+///  action: The pool can be imported using its name or numeric identifier.
+///  config:
+///
+///     tank        ONLINE
+///       mirror-0  ONLINE
+///         /tmp/a  ONLINE
+///         /tmp/b  ONLINE
+/// ```
+pub fn parse_import(output: &str) -> Result<Vec<ImportablePool>, ParseError> {
+    text_pools(output)?
+        .into_iter()
+        .map(|mut p| {
+            let id = p
+                .fields
+                .get("id")
+                .and_then(|id| id.trim().parse().ok())
+                .ok_or_else(|| ParseError::new(&p.name, "missing or invalid id"))?;
+
+            let destroyed = match p.fields.get_mut("state") {
+                Some(state) if state.contains("(DESTROYED)") => {
+                    *state = state.replace("(DESTROYED)", "");
+                    true
+                }
+                _ => false,
+            };
+
+            Ok(ImportablePool {
+                id,
+                destroyed,
+                pool: p.into_status(true)?,
+            })
+        })
+        .collect()
+}
+
+fn text_pools(output: &str) -> Result<Vec<TextPool>, ParseError> {
+    let mut pools = Vec::new();
+    let mut pool: Option<TextPool> = None;
+    let mut key = String::new();
+
+    for line in output.lines() {
+        let header = line
+            .trim_start_matches(' ')
+            .split_once(':')
+            .filter(|(k, _)| !line.starts_with('\t') && TEXT_KEYS.contains(k));
+
+        if let Some((k, v)) = header {
+            let v = v.trim();
+            if k == "pool" {
+                pools.extend(pool.take());
+                pool = Some(TextPool {
+                    name: v.to_owned(),
+                    ..Default::default()
+                });
+            }
+
+            let p = pool
+                .as_mut()
+                .ok_or_else(|| ParseError::new(line, "expected pool"))?;
+            key = k.to_owned();
+            p.fields.insert(key.clone(), v.to_owned());
+            continue;
+        }
+
+        let p = match pool.as_mut() {
+            Some(p) => p,
+            None if line.trim().is_empty() => continue,
+            None => return Err(ParseError::new(line, "expected pool")),
+        };
+
+        match key.as_str() {
+            "config" => {
+                if !line.trim().is_empty() {
+                    p.config.push(line.to_owned());
+                }
+            }
+            "errors" => {
+                if !line.trim().is_empty() {
+                    p.errors.push(line.trim().to_owned());
+                }
+            }
+            _ => {
+                let v = p.fields.entry(key.clone()).or_default();
+                v.push('\n');
+                v.push_str(line.trim());
+            }
+        }
+    }
+    pools.extend(pool);
+
+    Ok(pools)
+}
@@ -0,0 +1,105 @@
+//! Per-user, per-group, and per-project space accounting (`zfs userspace` and friends)
+
+use crate::space::parse_bytes;
+use crate::ParseError;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SpaceType {
+    /// `POSIX User`
+    PosixUser,
+    /// `POSIX Group`
+    PosixGroup,
+    /// `SMB User`
+    SmbUser,
+    /// `SMB Group`
+    SmbGroup,
+    /// `Project`
+    Project,
+}
+
+/// A single row of `zfs userspace`, `zfs groupspace`, or `zfs projectspace`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SpaceRow {
+    pub space_type: SpaceType,
+    /// User or group name, or the numeric id if it has no name (or names were not requested).
+    /// Always numeric for projects.
+    pub name: String,
+    /// Bytes used
+    pub used: u64,
+    /// Quota in bytes, if any
+    pub quota: Option<u64>,
+    /// Objects (files, directories, etc) used, if object accounting is enabled
+    pub objused: Option<u64>,
+    /// Object quota, if any
+    pub objquota: Option<u64>,
+}
+
+/// The fields to request with `-o` in the order `SpaceRow::parse_line` expects
+pub const FIELDS: &[&str] = &["type", "name", "used", "quota", "objused", "objquota"];
+
+/// Parse a quota field, which is `none` (or `-`) when unset. A quota of 0 also means unset.
+fn parse_quota(v: &str) -> Result<Option<u64>, &'static str> {
+    match v {
+        "none" | "0" => Ok(None),
+        _ => parse_bytes(v),
+    }
+}
+
+impl SpaceRow {
+    /// Parse a line of `zfs userspace -Hp -o <FIELDS>`
+    ///
+    /// ```text
+    /// POSIX User    alice    1048576    10737418240    12    none
+    /// ```
+    pub fn parse_line(line: &str) -> Result<Self, ParseError> {
+        let err = |msg| ParseError::new(line, msg);
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != FIELDS.len() {
+            return Err(err("unexpected number of columns"));
+        }
+
+        let space_type = match fields[0] {
+            "POSIX User" => SpaceType::PosixUser,
+            "POSIX Group" => SpaceType::PosixGroup,
+            "SMB User" => SpaceType::SmbUser,
+            "SMB Group" => SpaceType::SmbGroup,
+            "Project" => SpaceType::Project,
+            _ => return Err(err("unknown type")),
+        };
+
+        Ok(SpaceRow {
+            space_type,
+            name: fields[1].to_owned(),
+            used: parse_bytes(fields[2])
+                .map_err(err)?
+                .ok_or_else(|| err("missing used"))?,
+            quota: parse_quota(fields[3]).map_err(err)?,
+            objused: parse_bytes(fields[4]).map_err(err)?,
+            objquota: parse_quota(fields[5]).map_err(err)?,
+        })
+    }
+}
+
+/// Who a quota applies to
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum QuotaTarget<'a> {
+    /// User name or numeric id
+    User(&'a str),
+    /// Group name or numeric id
+    Group(&'a str),
+    /// Project id
+    Project(u64),
+}
+
+impl<'a> QuotaTarget<'a> {
+    /// The property (`userquota@alice`, `projectobjquota@5`, etc) which sets this quota. If
+    /// `objects`, the quota limits the number of objects rather than bytes.
+    pub fn property(&self, objects: bool) -> String {
+        let obj = if objects { "obj" } else { "" };
+        match self {
+            QuotaTarget::User(u) => format!("user{}quota@{}", obj, u),
+            QuotaTarget::Group(g) => format!("group{}quota@{}", obj, g),
+            QuotaTarget::Project(p) => format!("project{}quota@{}", obj, p),
+        }
+    }
+}
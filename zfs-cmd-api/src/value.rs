@@ -0,0 +1,145 @@
+//! Typed interpretation of property values, in either the human readable form `zfs` and `zpool`
+//! print by default (`1.75T`, `20%`, `1.00x`) or the parsable (`-p`) form.
+//!
+//! In all cases `-` (which indicates the property does not apply) parses as `None`.
+
+use crate::ParseError;
+
+/// A property value, with its type inferred from its (human readable) form
+#[derive(Debug, PartialEq, Clone)]
+pub enum PropertyValue {
+    /// `-`
+    None,
+    /// `1.75T`, `512B`
+    Bytes(u64),
+    /// `20%`
+    Percent(f64),
+    /// `1.00x`
+    Ratio(f64),
+    /// `on`, `off`, `yes`, `no`
+    Bool(bool),
+    /// A plain integer, which in parsable output may be a size, percentage, or count
+    Number(u64),
+    Text(String),
+}
+
+impl PropertyValue {
+    /// Infer the type of `v` from its form. Prefer the specific `parse_*` functions when the
+    /// property's type is known.
+    pub fn parse(v: &str) -> Self {
+        if v == "-" {
+            return PropertyValue::None;
+        }
+        if let Ok(n) = v.parse() {
+            return PropertyValue::Number(n);
+        }
+        if let Ok(Some(b)) = parse_bool(v) {
+            return PropertyValue::Bool(b);
+        }
+        if v.ends_with('%') {
+            if let Ok(Some(p)) = parse_percent(v) {
+                return PropertyValue::Percent(p);
+            }
+        }
+        if v.ends_with('x') {
+            if let Ok(Some(r)) = parse_ratio(v) {
+                return PropertyValue::Ratio(r);
+            }
+        }
+        if let Ok(Some(b)) = parse_size(v) {
+            return PropertyValue::Bytes(b);
+        }
+
+        PropertyValue::Text(v.to_owned())
+    }
+}
+
+fn parse_f64(v: &str) -> Result<f64, ParseError> {
+    match v.parse::<f64>() {
+        Ok(f) if f.is_finite() && f >= 0.0 => Ok(f),
+        _ => Err(ParseError::new(v, "invalid number")),
+    }
+}
+
+/// Parse a size in bytes: either exact (`1924145348608`) or human readable (`1.75T`, `601G`,
+/// `512B`). Human readable sizes use powers of 1024 and are rounded when printed, so the result
+/// is approximate.
+pub fn parse_size(v: &str) -> Result<Option<u64>, ParseError> {
+    if v == "-" {
+        return Ok(None);
+    }
+    if let Ok(n) = v.parse() {
+        return Ok(Some(n));
+    }
+
+    let err = || ParseError::new(v, "invalid size");
+    let num_end = v
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .ok_or_else(err)?;
+    let (num, unit) = v.split_at(num_end);
+    let shift = match unit.trim_end_matches("iB").trim_end_matches('B') {
+        "" => 0,
+        "K" | "k" => 10,
+        "M" | "m" => 20,
+        "G" | "g" => 30,
+        "T" | "t" => 40,
+        "P" | "p" => 50,
+        "E" | "e" => 60,
+        _ => return Err(err()),
+    };
+
+    let bytes = parse_f64(num).map_err(|_| err())? * (1u64 << shift) as f64;
+    if bytes >= u64::MAX as f64 {
+        return Err(err());
+    }
+    Ok(Some(bytes.round() as u64))
+}
+
+/// Parse a percentage: `33.6%`, or (in parsable output) `33`
+pub fn parse_percent(v: &str) -> Result<Option<f64>, ParseError> {
+    if v == "-" {
+        return Ok(None);
+    }
+    parse_f64(v.strip_suffix('%').unwrap_or(v)).map(Some)
+}
+
+/// Parse a ratio: `1.00x`, or (in parsable output) `1.00`
+pub fn parse_ratio(v: &str) -> Result<Option<f64>, ParseError> {
+    if v == "-" {
+        return Ok(None);
+    }
+    parse_f64(v.strip_suffix('x').unwrap_or(v)).map(Some)
+}
+
+/// Parse a boolean: `on`/`off` or `yes`/`no`
+pub fn parse_bool(v: &str) -> Result<Option<bool>, ParseError> {
+    match v {
+        "-" => Ok(None),
+        "on" | "yes" => Ok(Some(true)),
+        "off" | "no" => Ok(Some(false)),
+        _ => Err(ParseError::new(v, "invalid boolean")),
+    }
+}
+
+/// State of a pool feature (`feature@<name>` properties)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FeatureState {
+    /// Not usable until enabled with `zpool upgrade` or by setting it to `enabled`
+    Disabled,
+    /// Usable, but with no on-disk changes yet. Pools remain importable by systems without it.
+    Enabled,
+    /// In use on disk. Pools are only importable by systems supporting it (or, for some
+    /// features, read-only).
+    Active,
+}
+
+/// Parse a feature state: `disabled`, `enabled`, or `active`
+pub fn parse_feature(v: &str) -> Result<Option<FeatureState>, ParseError> {
+    match v {
+        "-" => Ok(None),
+        "disabled" => Ok(Some(FeatureState::Disabled)),
+        "enabled" => Ok(Some(FeatureState::Enabled)),
+        "active" => Ok(Some(FeatureState::Active)),
+        _ => Err(ParseError::new(v, "invalid feature state")),
+    }
+}
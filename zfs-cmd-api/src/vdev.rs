@@ -0,0 +1,242 @@
+//! Vdev specifications for `zpool add` (and `zpool create`), and the layouts `zpool add -n`
+//! reports
+
+use crate::ParseError;
+use std::collections::BTreeMap;
+
+/// Allocation class a vdev is added to
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VdevClass {
+    Normal,
+    /// `special`: metadata (and optionally small blocks)
+    Special,
+    /// `dedup`: dedup tables
+    Dedup,
+    /// `log`: separate intent log
+    Log,
+    /// `cache`: L2ARC. Only plain devices are allowed.
+    Cache,
+    /// `spare`: hot spares. Only plain devices are allowed.
+    Spare,
+}
+
+impl VdevClass {
+    fn keyword(&self) -> Option<&'static str> {
+        match self {
+            VdevClass::Normal => None,
+            VdevClass::Special => Some("special"),
+            VdevClass::Dedup => Some("dedup"),
+            VdevClass::Log => Some("log"),
+            VdevClass::Cache => Some("cache"),
+            VdevClass::Spare => Some("spare"),
+        }
+    }
+}
+
+/// Redundancy of a vdev
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VdevType {
+    /// Each device is its own top level vdev, without redundancy
+    Disk,
+    Mirror,
+    /// `raidz1`, `raidz2`, or `raidz3`
+    Raidz(u8),
+    /// `draid<parity>[:<data>d][:<children>c][:<spares>s]`
+    Draid {
+        parity: u8,
+        data: Option<u32>,
+        children: Option<u32>,
+        spares: Option<u32>,
+    },
+}
+
+impl VdevType {
+    fn keyword(&self) -> Option<String> {
+        match *self {
+            VdevType::Disk => None,
+            VdevType::Mirror => Some("mirror".to_owned()),
+            VdevType::Raidz(parity) => Some(format!("raidz{}", parity)),
+            VdevType::Draid {
+                parity,
+                data,
+                children,
+                spares,
+            } => {
+                let mut k = format!("draid{}", parity);
+                if let Some(d) = data {
+                    k.push_str(&format!(":{}d", d));
+                }
+                if let Some(c) = children {
+                    k.push_str(&format!(":{}c", c));
+                }
+                if let Some(s) = spares {
+                    k.push_str(&format!(":{}s", s));
+                }
+                Some(k)
+            }
+        }
+    }
+}
+
+/// One element of a vdev specification: a group of devices forming a vdev of `vdev_type` in
+/// `class`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct VdevSpec {
+    pub class: VdevClass,
+    pub vdev_type: VdevType,
+    pub devices: Vec<String>,
+}
+
+impl VdevSpec {
+    pub fn new<S: AsRef<str>>(class: VdevClass, vdev_type: VdevType, devices: &[S]) -> Self {
+        VdevSpec {
+            class,
+            vdev_type,
+            devices: devices.iter().map(|d| d.as_ref().to_owned()).collect(),
+        }
+    }
+
+    pub fn disks<S: AsRef<str>>(devices: &[S]) -> Self {
+        Self::new(VdevClass::Normal, VdevType::Disk, devices)
+    }
+
+    pub fn mirror<S: AsRef<str>>(devices: &[S]) -> Self {
+        Self::new(VdevClass::Normal, VdevType::Mirror, devices)
+    }
+
+    pub fn raidz<S: AsRef<str>>(parity: u8, devices: &[S]) -> Self {
+        Self::new(VdevClass::Normal, VdevType::Raidz(parity), devices)
+    }
+
+    /// Place this vdev in `class` instead of the normal class
+    pub fn class(mut self, class: VdevClass) -> Self {
+        self.class = class;
+        self
+    }
+
+    /// Check for combinations `zpool` will reject
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.devices.is_empty() {
+            return Err("vdev has no devices");
+        }
+
+        match self.vdev_type {
+            VdevType::Raidz(p) | VdevType::Draid { parity: p, .. } if !(1..=3).contains(&p) => {
+                return Err("parity must be 1, 2, or 3")
+            }
+            VdevType::Mirror if self.devices.len() < 2 => {
+                return Err("mirror needs at least 2 devices")
+            }
+            _ => {}
+        }
+
+        match (self.class, self.vdev_type) {
+            (VdevClass::Cache, VdevType::Disk) | (VdevClass::Spare, VdevType::Disk) => Ok(()),
+            (VdevClass::Cache, _) | (VdevClass::Spare, _) => {
+                Err("cache and spare vdevs must be plain devices")
+            }
+            (VdevClass::Log, VdevType::Raidz(_)) | (VdevClass::Log, VdevType::Draid { .. }) => {
+                Err("log vdevs can't be raidz or draid")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Arguments forming this part of the vdev specification
+    pub fn args(&self) -> Vec<String> {
+        self.class
+            .keyword()
+            .map(|k| k.to_owned())
+            .into_iter()
+            .chain(self.vdev_type.keyword())
+            .chain(self.devices.iter().cloned())
+            .collect()
+    }
+}
+
+/// A vdev (and its children) in the layout reported by `zpool add -n`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LayoutVdev {
+    pub name: String,
+    pub children: Vec<LayoutVdev>,
+}
+
+/// The configuration a pool would have after a `zpool add -n`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Layout {
+    /// The root vdev (named for the pool) with its normal class children
+    pub root: LayoutVdev,
+    /// Other groups (`logs`, `cache`, `spares`, `special`, `dedup`) and their members
+    pub sections: BTreeMap<String, Vec<LayoutVdev>>,
+}
+
+fn build_tree(items: &[(usize, String)], i: &mut usize, depth: usize) -> Vec<LayoutVdev> {
+    let mut out = Vec::new();
+    while *i < items.len() && items[*i].0 >= depth {
+        let (d, ref name) = items[*i];
+        *i += 1;
+        let children = build_tree(items, i, d + 1);
+        out.push(LayoutVdev {
+            name: name.clone(),
+            children,
+        });
+    }
+    out
+}
+
+/// Parse the output of `zpool add -n`
+///
+/// ```text
+/// would update 'tank' to the following configuration:
+///
+///     tank
+///       mirror-0
+///         sda
+///         sdb
+///     logs
+///       sdc
+/// ```
+pub fn parse_dry_run(output: &str) -> Result<Layout, ParseError> {
+    let mut lines = output.lines().filter(|l| !l.trim().is_empty());
+    let first = lines
+        .next()
+        .ok_or_else(|| ParseError::new(output, "empty output"))?;
+    if !first.starts_with("would update") {
+        return Err(ParseError::new(first, "unexpected header"));
+    }
+
+    let mut section = String::new();
+    let mut groups: Vec<(String, Vec<(usize, String)>)> = vec![(String::new(), Vec::new())];
+    for line in lines {
+        let body = line.trim_start_matches('\t');
+        let indent = body.len() - body.trim_start_matches(' ').len();
+        let name = body.trim().to_owned();
+
+        if indent == 0 && !groups[0].1.is_empty() {
+            section = name;
+            groups.push((section.clone(), Vec::new()));
+            continue;
+        }
+
+        let depth = if section.is_empty() {
+            indent / 2
+        } else {
+            (indent / 2).saturating_sub(1)
+        };
+        groups.last_mut().unwrap().1.push((depth, name));
+    }
+
+    let mut groups = groups.into_iter();
+    let (_, root_items) = groups.next().unwrap();
+    let root = build_tree(&root_items, &mut 0, 0)
+        .into_iter()
+        .next()
+        .ok_or_else(|| ParseError::new(output, "no root vdev"))?;
+
+    let mut sections = BTreeMap::new();
+    for (name, items) in groups {
+        sections.insert(name, build_tree(&items, &mut 0, 0));
+    }
+
+    Ok(Layout { root, sections })
+}
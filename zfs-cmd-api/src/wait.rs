@@ -0,0 +1,114 @@
+//! Activities `zfs wait` and `zpool wait` can wait for, and `zpool wait` progress output
+
+use crate::ParseError;
+
+/// How a wait ended
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WaitStatus {
+    /// The activities completed (or were not running)
+    Complete,
+    /// The timeout elapsed first. The activities may still be running.
+    TimedOut,
+}
+
+/// Activities `zfs wait` can wait for
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DatasetActivity {
+    /// `deleteq`: files unlinked while still open being freed
+    DeleteQueue,
+}
+
+impl DatasetActivity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DatasetActivity::DeleteQueue => "deleteq",
+        }
+    }
+}
+
+/// Activities `zpool wait` can wait for, in the order `zpool wait` prints their progress
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum PoolActivity {
+    /// `discard`: discarding a checkpoint
+    Discard,
+    /// `free`: freeing space from destroyed datasets
+    Free,
+    Initialize,
+    Replace,
+    /// `remove`: device removal
+    Remove,
+    Resilver,
+    Scrub,
+    Trim,
+    RaidzExpand,
+}
+
+/// Every `PoolActivity`, in the order `zpool wait` prints their progress
+pub const POOL_ACTIVITIES: &[PoolActivity] = &[
+    PoolActivity::Discard,
+    PoolActivity::Free,
+    PoolActivity::Initialize,
+    PoolActivity::Replace,
+    PoolActivity::Remove,
+    PoolActivity::Resilver,
+    PoolActivity::Scrub,
+    PoolActivity::Trim,
+    PoolActivity::RaidzExpand,
+];
+
+impl PoolActivity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PoolActivity::Discard => "discard",
+            PoolActivity::Free => "free",
+            PoolActivity::Initialize => "initialize",
+            PoolActivity::Replace => "replace",
+            PoolActivity::Remove => "remove",
+            PoolActivity::Resilver => "resilver",
+            PoolActivity::Scrub => "scrub",
+            PoolActivity::Trim => "trim",
+            PoolActivity::RaidzExpand => "raidz_expand",
+        }
+    }
+}
+
+/// The columns `zpool wait -t <activities>` prints: the requested activities (or all of them, if
+/// none were) in a fixed order.
+pub fn progress_columns(activities: &[PoolActivity]) -> Vec<PoolActivity> {
+    if activities.is_empty() {
+        return POOL_ACTIVITIES.to_vec();
+    }
+
+    let mut columns = activities.to_vec();
+    columns.sort();
+    columns.dedup();
+    columns
+}
+
+/// Parse a line of `zpool wait -Hp` progress: the bytes remaining for each of `columns`
+///
+/// ```text
+/// 0    1073741824    0
+/// ```
+pub fn parse_progress(
+    line: &str,
+    columns: &[PoolActivity],
+) -> Result<Vec<(PoolActivity, u64)>, ParseError> {
+    let err = |msg| ParseError::new(line, msg);
+    let values: Vec<&str> = line.split('\t').collect();
+    if values.len() != columns.len() {
+        return Err(err("unexpected number of columns"));
+    }
+
+    columns
+        .iter()
+        .zip(values)
+        .map(|(a, v)| match v {
+            "-" => Ok((*a, 0)),
+            _ => v
+                .parse()
+                .map(|v| (*a, v))
+                .map_err(|_| err("invalid number")),
+        })
+        .collect()
+}
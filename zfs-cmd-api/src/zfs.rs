@@ -1,15 +1,41 @@
-use enumflags2::BitFlags;
-use failure_derive::Fail;
-use log::{info, warn};
+use crate::allow::{Permissions, Scope, Who};
+use crate::destroy::{self, DestroyReport};
+use crate::diff::DiffRecord;
+use crate::encryption::{self, EncryptionInfo, KeySource, LoadKeyFlags};
+use crate::mount::{self, MountFlags, MountInfo, UnmountFlags};
+use crate::program::{self, ProgramOptions};
+use crate::space::{self, DestroyEstimate, SpaceUsage};
+use crate::userspace::{self, QuotaTarget, SpaceRow};
+use crate::wait::{DatasetActivity, WaitStatus};
+use crate::{audit, ParseError};
+use enumflags2::{bitflags, BitFlags};
 use std::env;
 use std::ffi::OsStr;
+use std::io::{BufRead, Read, Write};
 use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::{Duration, Instant};
 use std::{fmt, io};
+use tracing::{field, info, info_span, warn, Span};
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Zfs {
     // FIXME: we require utf-8 here
     zfs_cmd: Vec<String>,
+    read_prefix: Vec<String>,
+    mutate_prefix: Vec<String>,
+    audit_log: Option<PathBuf>,
+}
+
+/// Classes of operation which may be run with different command prefixes.
+///
+/// This allows, for example, running `list` unprivileged while `destroy` goes through `sudo`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OpClass {
+    /// Operations which do not change state: `list`, `get`, and `send`
+    Read,
+    /// Operations which change state: `recv`, `destroy`, `rollback`, `set`, `rename`, etc
+    Mutate,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -20,6 +46,43 @@ pub enum ListTypes {
     Bookmark,
 }
 
+/// Tracks a single execution of the `zfs` command.
+///
+/// Each execution gets its own span carrying the subcommand and dataset. The duration, exit
+/// status, and (for send/recv) byte count are recorded on the span once the command completes,
+/// which allows subscribers to aggregate operation latency per dataset.
+#[derive(Debug)]
+struct CmdTrace {
+    span: Span,
+    start: Instant,
+}
+
+impl CmdTrace {
+    fn new(subcommand: &'static str, dataset: Option<&str>) -> Self {
+        CmdTrace {
+            span: info_span!(
+                "zfs",
+                subcommand,
+                dataset,
+                duration_ms = field::Empty,
+                status = field::Empty,
+                bytes = field::Empty,
+            ),
+            start: Instant::now(),
+        }
+    }
+
+    fn record_bytes(&self, bytes: u64) {
+        self.span.record("bytes", bytes);
+    }
+
+    fn finish(&self, status: &process::ExitStatus) {
+        self.span
+            .record("duration_ms", self.start.elapsed().as_millis() as u64);
+        self.span.record("status", status.code());
+    }
+}
+
 #[derive(Debug)]
 pub struct CmdInfo {
     status: process::ExitStatus,
@@ -27,38 +90,80 @@ pub struct CmdInfo {
     cmd: String,
 }
 
-#[derive(Debug, Fail)]
+impl CmdInfo {
+    pub fn status(&self) -> process::ExitStatus {
+        self.status
+    }
+
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    /// The command line, as run
+    pub fn cmd(&self) -> &str {
+        &self.cmd
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
 pub enum ZfsError {
-    #[fail(display = "execution of zfs command failed: {}", io)]
-    Exec { io: io::Error },
+    #[error("execution of zfs command failed: {io}")]
+    Exec {
+        #[from]
+        io: io::Error,
+    },
 
-    #[fail(display = "zfs command returned an error: {:?}", cmd_info)]
+    #[error("zfs command returned an error: {cmd_info:?}")]
     Process { cmd_info: CmdInfo },
 
     // A specific CannotOpen kind
-    #[fail(display = "no such dataset '{}' ({:?})", dataset, cmd_info)]
+    #[error("no such dataset '{dataset}' ({cmd_info:?})")]
     NoDataset { dataset: String, cmd_info: CmdInfo },
 
-    #[fail(display = "cannot open: {:?}", cmd_info)]
+    #[error("cannot open: {cmd_info:?}")]
     CannotOpen { cmd_info: CmdInfo },
 
-    #[fail(
-        display = "cannot resume send of nonexistent dataset '{}' ({:?})",
-        dataset, cmd_info
-    )]
+    #[error("cannot resume send of nonexistent dataset '{dataset}' ({cmd_info:?})")]
     CannotResumeSendDoesNotExist { dataset: String, cmd_info: CmdInfo },
 
-    #[fail(display = "cannot resume send: {:?}", cmd_info)]
+    #[error("cannot resume send: {cmd_info:?}")]
     CannotResumeSend { cmd_info: CmdInfo },
 
-    #[fail(display = "cannot recv: failed to read stream ({:?})", cmd_info)]
+    #[error("cannot recv: failed to read stream ({cmd_info:?})")]
     CannotRecvFailedToRead { cmd_info: CmdInfo },
 
-    #[fail(display = "cannot recv new fs: {:?}", cmd_info)]
+    #[error("cannot recv new fs: {cmd_info:?}")]
     CannotRecvNewFs { cmd_info: CmdInfo },
+
+    #[error("incorrect key provided for '{dataset}' ({cmd_info:?})")]
+    IncorrectKey { dataset: String, cmd_info: CmdInfo },
+
+    #[error("key already loaded for '{dataset}' ({cmd_info:?})")]
+    KeyAlreadyLoaded { dataset: String, cmd_info: CmdInfo },
+
+    #[error("key already unloaded for '{dataset}' ({cmd_info:?})")]
+    KeyAlreadyUnloaded { dataset: String, cmd_info: CmdInfo },
+
+    #[error("cannot unmount '{target}': busy ({cmd_info:?})")]
+    Busy { target: String, cmd_info: CmdInfo },
+
+    #[error("cannot unmount '{target}' ({cmd_info:?})")]
+    CannotUnmount { target: String, cmd_info: CmdInfo },
+
+    #[error("channel program failed: {error} ({cmd_info:?})")]
+    ChannelProgram { error: String, cmd_info: CmdInfo },
+
+    #[error("could not open audit log: {io}")]
+    Audit { io: io::Error },
+
+    #[error("could not parse output of zfs {cmd}: {error}")]
+    Parse {
+        cmd: &'static str,
+        error: ParseError,
+    },
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct ZfsList {
     out: Vec<u8>,
 }
@@ -73,15 +178,9 @@ impl fmt::Display for ZfsList {
     }
 }
 
-impl Default for ZfsList {
-    fn default() -> Self {
-        ZfsList { out: Vec::new() }
-    }
-}
-
 impl ZfsList {
     pub fn iter(&self) -> impl Iterator<Item = &[u8]> {
-        self.out.split(|&x| x == b'\n').filter(|x| x.len() != 0)
+        self.out.split(|&x| x == b'\n').filter(|x| !x.is_empty())
     }
 }
 
@@ -139,19 +238,14 @@ impl<'a> From<&'a TypeSpec> for String {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 enum ListRecurse {
+    #[default]
     No,
     Depth(usize),
     Yes,
 }
 
-impl Default for ListRecurse {
-    fn default() -> Self {
-        ListRecurse::No
-    }
-}
-
 /// Note: no support for sorting, folks can do that in rust if they really want it.
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct ListBuilder {
@@ -200,8 +294,8 @@ impl ListBuilder {
         self
     }
 
-    pub fn with_elements(&mut self, mut elements: &[&'static str]) -> &mut Self {
-        self.elements.extend_from_slice(&mut elements);
+    pub fn with_elements(&mut self, elements: &[&'static str]) -> &mut Self {
+        self.elements.extend_from_slice(elements);
         self
     }
 
@@ -253,9 +347,9 @@ fn cmdinfo_to_error(cmd_info: CmdInfo) -> ZfsError {
         return match error {
             "dataset does not exist\n" => ZfsError::NoDataset {
                 dataset: ds.to_owned(),
-                cmd_info: cmd_info,
+                cmd_info,
             },
-            _ => ZfsError::CannotOpen { cmd_info: cmd_info },
+            _ => ZfsError::CannotOpen { cmd_info },
         };
     }
 
@@ -274,10 +368,10 @@ fn cmdinfo_to_error(cmd_info: CmdInfo) -> ZfsError {
             "used in the initial send no longer exists\n" => {
                 ZfsError::CannotResumeSendDoesNotExist {
                     dataset: ds.to_owned(),
-                    cmd_info: cmd_info,
+                    cmd_info,
                 }
             }
-            _ => ZfsError::CannotResumeSend { cmd_info: cmd_info },
+            _ => ZfsError::CannotResumeSend { cmd_info },
         };
     }
 
@@ -289,7 +383,7 @@ fn cmdinfo_to_error(cmd_info: CmdInfo) -> ZfsError {
     // note: run with `RUST_BACKTRACE=1` environment variable to display a backtra
     let prefix_crnfs = "cannot receive new filesystem stream: ";
     if cmd_info.stderr.starts_with(prefix_crnfs) {
-        return ZfsError::CannotRecvNewFs { cmd_info: cmd_info };
+        return ZfsError::CannotRecvNewFs { cmd_info };
     }
 
     // XXX: avoided by fixing createtxg sort
@@ -319,54 +413,262 @@ fn cmdinfo_to_error(cmd_info: CmdInfo) -> ZfsError {
     //  thread 'main' panicked at 'called `Result::unwrap()` on an `Err` value: Custom { kind: Other, error: "send or recv failed: Some(1), Some(1)" }', src/libcore/result.rs:1084:5
     //  note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace.
 
+    // Key load error: Incorrect key provided for 'tank/enc'.
+    // Key load error: Key already loaded for 'tank/enc'.
+    // Key unload error: Key already unloaded for 'tank/enc'.
+    //
+    // with `-r`, one of these is emitted per dataset followed by a summary line.
+    for line in cmd_info.stderr.lines() {
+        let aux = match line
+            .strip_prefix("Key load error: ")
+            .or_else(|| line.strip_prefix("Key unload error: "))
+        {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let dataset = match aux.find(" for '") {
+            Some(i) => aux[i + " for '".len()..].trim_end_matches("'.").to_owned(),
+            None => continue,
+        };
+
+        if aux.starts_with("Incorrect key provided") {
+            return ZfsError::IncorrectKey { dataset, cmd_info };
+        } else if aux.starts_with("Key already loaded") {
+            return ZfsError::KeyAlreadyLoaded { dataset, cmd_info };
+        } else if aux.starts_with("Key already unloaded") {
+            return ZfsError::KeyAlreadyUnloaded { dataset, cmd_info };
+        }
+    }
+
+    // umount: /tank/home: target is busy.
+    // cannot unmount '/tank/home': umount failed
+    //
+    // cannot unmount '/tank/home': pool or dataset is busy
+    if let Some((target, reason)) = mount::parse_unmount_error(&cmd_info.stderr) {
+        return if reason.contains("busy") || cmd_info.stderr.contains("target is busy") {
+            ZfsError::Busy { target, cmd_info }
+        } else {
+            ZfsError::CannotUnmount { target, cmd_info }
+        };
+    }
+
+    // Channel program execution failed:
+    // [string "channel program"]:1: attempt to call a nil value (field 'foo')
+    if let Some(error) = program::parse_error(&cmd_info.stderr) {
+        return ZfsError::ChannelProgram { error, cmd_info };
+    }
+
     match cmd_info.stderr.as_ref() {
         "cannot receive: failed to read from stream\n" => {
-            ZfsError::CannotRecvFailedToRead { cmd_info: cmd_info }
+            ZfsError::CannotRecvFailedToRead { cmd_info }
         }
         _ => {
             // generic error
-            ZfsError::Process { cmd_info: cmd_info }
+            ZfsError::Process { cmd_info }
+        }
+    }
+}
+
+fn output_with_input(cmd: &mut process::Command, input: &[u8]) -> io::Result<process::Output> {
+    let mut child = cmd
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()?;
+
+    // inputs here are small (ie: keys), so writing them entirely before reading any output is fine
+    let r = child.stdin.take().unwrap().write_all(input);
+    let output = child.wait_with_output()?;
+    r.map(|_| output)
+}
+
+fn key_source_args<'a>(cmd: &mut process::Command, source: KeySource<'a>) -> Option<&'a [u8]> {
+    match source {
+        KeySource::Property => None,
+        KeySource::Location(l) => {
+            cmd.arg("-L").arg(l);
+            None
+        }
+        KeySource::Data(d) => {
+            cmd.arg("-L").arg("prompt");
+            Some(d)
         }
     }
 }
 
+fn delegation_args(cmd: &mut process::Command, who: &Who, scope: Scope, permissions: &[&str]) {
+    match scope {
+        Scope::Local => {
+            cmd.arg("-l");
+        }
+        Scope::Descendent => {
+            cmd.arg("-d");
+        }
+        Scope::LocalAndDescendent => {}
+    }
+
+    match who {
+        Who::User(u) => cmd.arg("-u").arg(u),
+        Who::Group(g) => cmd.arg("-g").arg(g),
+        Who::Everyone => cmd.arg("-e"),
+    };
+
+    cmd.arg(permissions.join(","));
+}
+
 impl Zfs {
-    fn cmd(&self) -> process::Command {
-        let mut cmd = process::Command::new(&self.zfs_cmd[0]);
-        cmd.args(&self.zfs_cmd[1..]);
-        cmd
+    /// Build a command running `zfs` for `class`, with that class's prefix placed immediately
+    /// before the final word of the zfs command. With `ssh host zfs` and a `sudo` prefix, this
+    /// runs `ssh host sudo zfs`, so the prefix applies on the host running `zfs`.
+    fn cmd(&self, class: OpClass) -> io::Result<process::Command> {
+        let prefix = match class {
+            OpClass::Read => &self.read_prefix,
+            OpClass::Mutate => &self.mutate_prefix,
+        };
+
+        let (zfs, remote) = self
+            .zfs_cmd
+            .split_last()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty zfs command"))?;
+        let mut args = remote.iter().chain(prefix.iter());
+        Ok(match args.next() {
+            Some(program) => {
+                let mut cmd = process::Command::new(program);
+                cmd.args(args).arg(zfs);
+                cmd
+            }
+            None => process::Command::new(zfs),
+        })
     }
 
-    fn run_output(&self, mut cmd: process::Command) -> Result<std::process::Output, ZfsError> {
+    fn run_output(
+        &self,
+        trace: CmdTrace,
+        cmd: process::Command,
+    ) -> Result<std::process::Output, ZfsError> {
+        self.run_output_input(trace, cmd, None)
+    }
+
+    /// Like `run_output()`, but supply `input` on the command's stdin
+    fn run_output_input(
+        &self,
+        trace: CmdTrace,
+        mut cmd: process::Command,
+        input: Option<&[u8]>,
+    ) -> Result<std::process::Output, ZfsError> {
+        let _enter = trace.span.enter();
         info!("run: {:?}", cmd);
 
-        let output = cmd.output().map_err(|e| ZfsError::Exec { io: e })?;
+        let output = match input {
+            None => cmd.output(),
+            Some(input) => output_with_input(&mut cmd, input),
+        }
+        .map_err(|e| ZfsError::Exec { io: e })?;
+        trace.finish(&output.status);
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr[..]).into_owned();
 
             let cmd_info = CmdInfo {
                 status: output.status,
-                stderr: stderr,
+                stderr,
                 cmd: format!("{:?}", cmd),
             };
 
             return Err(cmdinfo_to_error(cmd_info));
         }
 
-        if output.stderr.len() > 0 {
+        if !output.stderr.is_empty() {
             warn!("stderr: {}", String::from_utf8_lossy(&output.stderr));
         }
 
         Ok(output)
     }
 
+    /// Run a command which changes state, recording it in the audit log (if one is configured).
+    ///
+    /// `affected` names the dataset whose snapshots may be removed by the command, and whether
+    /// the removal may extend to its children. Those snapshots are listed before running the
+    /// command so the audit record can show what existed prior.
+    fn run_audited(
+        &self,
+        trace: CmdTrace,
+        cmd: process::Command,
+        affected: Option<(&str, bool)>,
+    ) -> Result<std::process::Output, ZfsError> {
+        self.run_audited_input(trace, cmd, affected, None)
+    }
+
+    /// Like `run_audited()`, but supply `input` on the command's stdin
+    fn run_audited_input(
+        &self,
+        trace: CmdTrace,
+        cmd: process::Command,
+        affected: Option<(&str, bool)>,
+        input: Option<&[u8]>,
+    ) -> Result<std::process::Output, ZfsError> {
+        let pending = match self.audit_pending(&cmd, affected) {
+            Ok(Some(p)) => p,
+            Ok(None) => return self.run_output_input(trace, cmd, input),
+            Err(io) => return Err(ZfsError::Audit { io }),
+        };
+
+        let r = self.run_output_input(trace, cmd, input);
+        pending.finish(match r {
+            Ok(_) => audit::Outcome::Success,
+            Err(ref e) => audit::Outcome::Failed {
+                error: e.to_string(),
+            },
+        });
+        r
+    }
+
+    fn audit_pending(
+        &self,
+        cmd: &process::Command,
+        affected: Option<(&str, bool)>,
+    ) -> io::Result<Option<audit::Pending>> {
+        let path = match self.audit_log {
+            Some(ref p) => p,
+            None => return Ok(None),
+        };
+
+        let snapshots_before =
+            affected.and_then(|(dataset, recursive)| self.snapshots_of(dataset, recursive));
+        audit::Pending::open(path, cmd, snapshots_before).map(Some)
+    }
+
+    /// List the snapshots of the filesystem `dataset` refers to, returning `None` if they can't
+    /// be listed (for example, because the filesystem does not exist yet).
+    fn snapshots_of(&self, dataset: &str, recursive: bool) -> Option<Vec<String>> {
+        let fs = dataset.split(['@', '#']).next().unwrap();
+
+        let mut builder = ListBuilder::default();
+        builder
+            .include_snapshots()
+            .with_elements(&["name"])
+            .with_dataset(fs);
+        if recursive {
+            builder.recursive();
+        } else {
+            builder.depth(1);
+        }
+
+        let list = self.list_from_builder(&builder).ok()?;
+        Some(
+            list.iter()
+                .map(|v| String::from_utf8_lossy(v).into_owned())
+                .collect(),
+        )
+    }
+
     pub fn list_from_builder(&self, builder: &ListBuilder) -> Result<ZfsList, ZfsError> {
         // zfs list -H
         // '-s <prop>' sort by property (multiple allowed)
         // '-d <depth>' recurse to depth
         // '-r'
-        let mut cmd = self.cmd();
+        let mut cmd = self.cmd(OpClass::Read)?;
 
         cmd.arg("list")
             // +parsable, +scripting mode
@@ -376,7 +678,7 @@ impl Zfs {
             .arg("-s")
             .arg("name");
 
-        if builder.elements.len() == 0 {
+        if builder.elements.is_empty() {
             cmd
                 // only name
                 .arg("-o")
@@ -402,10 +704,10 @@ impl Zfs {
         }
 
         match &builder.dataset_types {
-            &None => {
+            None => {
                 // TODO: should we require this?
             }
-            &Some(ref v) => {
+            Some(v) => {
                 cmd.arg("-t").arg(String::from(v));
             }
         }
@@ -417,7 +719,8 @@ impl Zfs {
             }
         }
 
-        let output = self.run_output(cmd)?;
+        let trace = CmdTrace::new("list", builder.base_dataset.as_deref());
+        let output = self.run_output(trace, cmd)?;
 
         Ok(ZfsList { out: output.stdout })
     }
@@ -436,7 +739,7 @@ impl Zfs {
         flags: BitFlags<DestroyFlags>,
         dataset: &str,
     ) -> Result<std::process::Output, ZfsError> {
-        let mut cmd = self.cmd();
+        let mut cmd = self.cmd(OpClass::Mutate)?;
         cmd.arg("destroy");
 
         if !flags.is_empty() {
@@ -449,13 +752,722 @@ impl Zfs {
                     DestroyFlags::MachineParsable => 'p',
                     DestroyFlags::RecursiveChildren => 'r',
                     DestroyFlags::Verbose => 'v',
+                    DestroyFlags::Defer => 'd',
+                });
+            }
+
+            cmd.arg(opts);
+        }
+        cmd.arg(dataset);
+
+        let trace = CmdTrace::new("destroy", Some(dataset));
+        if flags.contains(DestroyFlags::DryRun) {
+            self.run_output(trace, cmd)
+        } else {
+            let recursive = flags
+                .intersects(DestroyFlags::RecursiveChildren | DestroyFlags::RecursiveDependents);
+            self.run_audited(trace, cmd, Some((dataset, recursive)))
+        }
+    }
+
+    /// Destroy many `snapshots` (names without the `filesystem@` prefix) of `filesystem` using as
+    /// few `zfs destroy` invocations as the argument length limit allows.
+    ///
+    /// If `order` lists every snapshot of `filesystem` in `createtxg` order, runs of adjacent
+    /// snapshots are named as ranges (`fs@a%d`). Pass an empty `order` to name each snapshot.
+    ///
+    /// `zfs` destroys the snapshots named in one invocation all together or not at all. When some
+    /// can't be destroyed (for example, because they are held or cloned), they are recorded in
+    /// the returned report and the rest are retried without them.
+    pub fn destroy_snapshots(
+        &self,
+        flags: BitFlags<DestroyFlags>,
+        filesystem: &str,
+        snapshots: &[&str],
+        order: &[&str],
+    ) -> Result<DestroyReport, ZfsError> {
+        let mut report = DestroyReport::default();
+        let sorted = destroy::sort_by_order(snapshots, order);
+
+        for mut chunk in destroy::chunk_snapshots(filesystem, &sorted, destroy::MAX_ARG_LEN) {
+            while !chunk.is_empty() {
+                let arg = format!(
+                    "{}@{}",
+                    filesystem,
+                    destroy::snapshot_specs(&chunk, order).join(",")
+                );
+
+                let cmd_info = match self.destroy(flags, &arg) {
+                    Ok(_) => {
+                        report
+                            .destroyed
+                            .extend(chunk.iter().map(|snap| format!("{}@{}", filesystem, snap)));
+                        break;
+                    }
+                    Err(ZfsError::Process { cmd_info }) => cmd_info,
+                    Err(e) => return Err(e),
+                };
+
+                let before = chunk.len();
+                for failure in destroy::parse_failures(&cmd_info.stderr) {
+                    let snap = match failure.snapshot.split_once('@') {
+                        Some((fs, snap)) if fs == filesystem => snap,
+                        _ => continue,
+                    };
+
+                    if let Some(i) = chunk.iter().position(|s| *s == snap) {
+                        chunk.remove(i);
+                        report.failed.push(failure);
+                    }
+                }
+
+                // no failures we can attribute to a snapshot: retrying won't help
+                if chunk.len() == before {
+                    return Err(ZfsError::Process { cmd_info });
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Estimate the space freed by destroying `snapshots` without destroying anything.
+    ///
+    /// `snapshots` may name a single snapshot (`fs@a`), a list (`fs@a,b`), or a range of
+    /// snapshots (`fs@a%c`, where either end may be omitted).
+    pub fn destroy_estimate(&self, snapshots: &str) -> Result<DestroyEstimate, ZfsError> {
+        let output = self.destroy(
+            DestroyFlags::DryRun | DestroyFlags::Verbose | DestroyFlags::MachineParsable,
+            snapshots,
+        )?;
+
+        DestroyEstimate::parse(&String::from_utf8_lossy(&output.stdout)).map_err(|error| {
+            ZfsError::Parse {
+                cmd: "destroy",
+                error,
+            }
+        })
+    }
+
+    /// Roll `snapshot`'s filesystem back to `snapshot`
+    pub fn rollback(&self, flags: BitFlags<RollbackFlags>, snapshot: &str) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("rollback");
+
+        if !flags.is_empty() {
+            let mut opts = "-".to_owned();
+            for flag in flags.iter() {
+                opts.push(match flag {
+                    RollbackFlags::DestroyLater => 'r',
+                    RollbackFlags::DestroyLaterAndClones => 'R',
+                    RollbackFlags::ForceUmount => 'f',
+                });
+            }
+
+            cmd.arg(opts);
+        }
+        cmd.arg(snapshot);
+
+        self.run_audited(
+            CmdTrace::new("rollback", Some(snapshot)),
+            cmd,
+            Some((snapshot, false)),
+        )?;
+        Ok(())
+    }
+
+    /// Create the filesystem `dataset` with `props` set on it
+    pub fn create(
+        &self,
+        flags: BitFlags<CreateFlags>,
+        props: &[(&str, &str)],
+        dataset: &str,
+    ) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("create");
+
+        if !flags.is_empty() {
+            let mut opts = "-".to_owned();
+            for flag in flags.iter() {
+                opts.push(match flag {
+                    CreateFlags::CreateParents => 'p',
+                    CreateFlags::NoMount => 'u',
+                });
+            }
+
+            cmd.arg(opts);
+        }
+        for prop in props.iter() {
+            cmd.arg("-o").arg(format!("{}={}", prop.0, prop.1));
+        }
+        cmd.arg(dataset);
+
+        self.run_audited(CmdTrace::new("create", Some(dataset)), cmd, None)?;
+        Ok(())
+    }
+
+    /// Set one or more properties on `dataset`
+    pub fn set(&self, dataset: &str, props: &[(&str, &str)]) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("set");
+
+        for prop in props.iter() {
+            cmd.arg(format!("{}={}", prop.0, prop.1));
+        }
+        cmd.arg(dataset);
+
+        self.run_audited(CmdTrace::new("set", Some(dataset)), cmd, None)?;
+        Ok(())
+    }
+
+    pub fn rename(
+        &self,
+        flags: BitFlags<RenameFlags>,
+        from: &str,
+        to: &str,
+    ) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("rename");
+
+        if !flags.is_empty() {
+            let mut opts = "-".to_owned();
+            for flag in flags.iter() {
+                opts.push(match flag {
+                    RenameFlags::Recursive => 'r',
+                    RenameFlags::ForceUmount => 'f',
+                    RenameFlags::CreateParents => 'p',
+                    RenameFlags::NoRemount => 'u',
+                });
+            }
+
+            cmd.arg(opts);
+        }
+        cmd.arg(from).arg(to);
+
+        self.run_audited(CmdTrace::new("rename", Some(from)), cmd, None)?;
+        Ok(())
+    }
+
+    /// List the permissions delegated on `dataset` (and its ancestors) with `zfs allow`
+    pub fn allowed(&self, dataset: &str) -> Result<Permissions, ZfsError> {
+        let mut cmd = self.cmd(OpClass::Read)?;
+        cmd.arg("allow").arg(dataset);
+
+        let output = self.run_output(CmdTrace::new("allow", Some(dataset)), cmd)?;
+        Permissions::parse(&String::from_utf8_lossy(&output.stdout)).map_err(|error| {
+            ZfsError::Parse {
+                cmd: "allow",
+                error,
+            }
+        })
+    }
+
+    /// Delegate `permissions` on `dataset` to `who`
+    pub fn allow(
+        &self,
+        dataset: &str,
+        who: &Who,
+        scope: Scope,
+        permissions: &[&str],
+    ) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("allow");
+        delegation_args(&mut cmd, who, scope, permissions);
+        cmd.arg(dataset);
+
+        self.run_audited(CmdTrace::new("allow", Some(dataset)), cmd, None)?;
+        Ok(())
+    }
+
+    /// Remove delegated `permissions` on `dataset` from `who`
+    pub fn unallow(
+        &self,
+        dataset: &str,
+        who: &Who,
+        scope: Scope,
+        permissions: &[&str],
+    ) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("unallow");
+        delegation_args(&mut cmd, who, scope, permissions);
+        cmd.arg(dataset);
+
+        self.run_audited(CmdTrace::new("unallow", Some(dataset)), cmd, None)?;
+        Ok(())
+    }
+
+    /// Get the parsable values of `properties` on `dataset`, in the same order as `properties`.
+    ///
+    /// Unlike `list`, this allows properties which are not known ahead of time, like
+    /// `written@<snap>` or `userquota@<user>`.
+    pub fn get_values(&self, dataset: &str, properties: &[&str]) -> Result<Vec<String>, ZfsError> {
+        let mut cmd = self.cmd(OpClass::Read)?;
+        cmd.arg("get")
+            .arg("-Hp")
+            .arg("-o")
+            .arg("value")
+            .arg(properties.join(","))
+            .arg(dataset);
+
+        let output = self.run_output(CmdTrace::new("get", Some(dataset)), cmd)?;
+        let values: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.to_owned())
+            .collect();
+
+        if values.len() != properties.len() {
+            return Err(ZfsError::Parse {
+                cmd: "get",
+                error: ParseError::new(
+                    &values.join("\n"),
+                    "number of values does not match properties",
+                ),
+            });
+        }
+
+        Ok(values)
+    }
+
+    /// List the space accounting of `dataset` (and, if `recursive`, its descendants)
+    pub fn space(&self, dataset: &str, recursive: bool) -> Result<Vec<SpaceUsage>, ZfsError> {
+        let mut builder = ListBuilder::default();
+        builder
+            .include_filesystems()
+            .include_volumes()
+            .with_elements(space::LIST_ELEMENTS)
+            .with_dataset(dataset);
+        if recursive {
+            builder.recursive();
+        }
+
+        let rows: Vec<Vec<String>> = From::from(&self.list_from_builder(&builder)?);
+        rows.iter()
+            .map(|row| SpaceUsage::from_list_row(row))
+            .collect::<Result<_, _>>()
+            .map_err(|error| ZfsError::Parse { cmd: "list", error })
+    }
+
+    /// Bytes written to `dataset` since `snapshot` (`written@<snapshot>`). `snapshot` may be
+    /// given as just the snapshot name or in full.
+    pub fn written_since(&self, dataset: &str, snapshot: &str) -> Result<u64, ZfsError> {
+        let snap = snapshot.rsplit('@').next().unwrap();
+        let prop = format!("written@{}", snap);
+        let v = self.get_values(dataset, &[&prop])?;
+
+        space::parse_bytes(&v[0])
+            .ok()
+            .flatten()
+            .ok_or_else(|| ZfsError::Parse {
+                cmd: "get",
+                error: ParseError::new(&v[0], "invalid written value"),
+            })
+    }
+
+    fn space_rows(
+        &self,
+        subcommand: &'static str,
+        dataset: &str,
+        numeric: bool,
+    ) -> Result<Vec<SpaceRow>, ZfsError> {
+        let mut cmd = self.cmd(OpClass::Read)?;
+        cmd.arg(subcommand)
+            .arg(if numeric { "-Hpn" } else { "-Hp" })
+            .arg("-o")
+            .arg(userspace::FIELDS.join(","))
+            .arg(dataset);
+
+        let output = self.run_output(CmdTrace::new(subcommand, Some(dataset)), cmd)?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(SpaceRow::parse_line)
+            .collect::<Result<_, _>>()
+            .map_err(|error| ZfsError::Parse {
+                cmd: subcommand,
+                error,
+            })
+    }
+
+    /// Space used by each user in `dataset`. If `numeric`, users are identified by uid rather
+    /// than name.
+    pub fn userspace(&self, dataset: &str, numeric: bool) -> Result<Vec<SpaceRow>, ZfsError> {
+        self.space_rows("userspace", dataset, numeric)
+    }
+
+    /// Space used by each group in `dataset`. If `numeric`, groups are identified by gid rather
+    /// than name.
+    pub fn groupspace(&self, dataset: &str, numeric: bool) -> Result<Vec<SpaceRow>, ZfsError> {
+        self.space_rows("groupspace", dataset, numeric)
+    }
+
+    /// Space used by each project in `dataset`
+    pub fn projectspace(&self, dataset: &str) -> Result<Vec<SpaceRow>, ZfsError> {
+        self.space_rows("projectspace", dataset, false)
+    }
+
+    /// Set (or, with `None`, remove) the quota on `target` in `dataset`. If `objects`, the quota
+    /// limits the number of objects instead of bytes.
+    pub fn set_quota(
+        &self,
+        dataset: &str,
+        target: QuotaTarget<'_>,
+        objects: bool,
+        quota: Option<u64>,
+    ) -> Result<(), ZfsError> {
+        let value = match quota {
+            Some(q) => q.to_string(),
+            None => "none".to_owned(),
+        };
+        self.set(dataset, &[(&target.property(objects), &value)])
+    }
+
+    /// List the mount state of the filesystem `dataset` (and, if `recursive`, its descendants)
+    pub fn mounts(&self, dataset: &str, recursive: bool) -> Result<Vec<MountInfo>, ZfsError> {
+        let mut builder = ListBuilder::default();
+        builder
+            .include_filesystems()
+            .with_elements(mount::LIST_ELEMENTS)
+            .with_dataset(dataset);
+        if recursive {
+            builder.recursive();
+        }
+
+        let rows: Vec<Vec<String>> = From::from(&self.list_from_builder(&builder)?);
+        rows.iter()
+            .map(|row| MountInfo::from_list_row(row))
+            .collect::<Result<_, _>>()
+            .map_err(|error| ZfsError::Parse { cmd: "list", error })
+    }
+
+    /// Mount `dataset`, or with `None`, all filesystems which are mounted automatically (`-a`).
+    ///
+    /// `options` are temporary mount options (ie: `ro`), passed with `-o`.
+    pub fn mount(
+        &self,
+        flags: BitFlags<MountFlags>,
+        options: &[&str],
+        dataset: Option<&str>,
+    ) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("mount");
+
+        if !flags.is_empty() {
+            let mut opts = "-".to_owned();
+            for flag in flags.iter() {
+                opts.push(match flag {
+                    MountFlags::Overlay => 'O',
+                    MountFlags::LoadKeys => 'l',
+                    MountFlags::Force => 'f',
                 });
             }
 
             cmd.arg(opts);
         }
+        if !options.is_empty() {
+            cmd.arg("-o").arg(options.join(","));
+        }
+        match dataset {
+            Some(ds) => cmd.arg(ds),
+            None => cmd.arg("-a"),
+        };
+
+        self.run_output(CmdTrace::new("mount", dataset), cmd)?;
+        Ok(())
+    }
+
+    /// Unmount `target` (a filesystem or mountpoint), or with `None`, all filesystems (`-a`).
+    ///
+    /// Fails with `ZfsError::Busy` if a filesystem is in use (and `UnmountFlags::Force` was not
+    /// given).
+    pub fn unmount(
+        &self,
+        flags: BitFlags<UnmountFlags>,
+        target: Option<&str>,
+    ) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("unmount");
+
+        if !flags.is_empty() {
+            let mut opts = "-".to_owned();
+            for flag in flags.iter() {
+                opts.push(match flag {
+                    UnmountFlags::Force => 'f',
+                    UnmountFlags::UnloadKeys => 'u',
+                });
+            }
+
+            cmd.arg(opts);
+        }
+        match target {
+            Some(t) => cmd.arg(t),
+            None => cmd.arg("-a"),
+        };
+
+        self.run_output(CmdTrace::new("unmount", target), cmd)?;
+        Ok(())
+    }
+
+    /// Run the Lua channel program `script` on `pool` with `args`, returning the value the
+    /// program returns (`Value::Null` if none).
+    ///
+    /// Errors raised by the program (including exceeding its limits) are reported as
+    /// `ZfsError::ChannelProgram`.
+    pub fn program(
+        &self,
+        options: &ProgramOptions,
+        pool: &str,
+        script: &str,
+        args: &[&str],
+    ) -> Result<serde_json::Value, ZfsError> {
+        let mut cmd = self.cmd(if options.read_only {
+            OpClass::Read
+        } else {
+            OpClass::Mutate
+        })?;
+        cmd.arg("program")
+            .arg(if options.read_only { "-jn" } else { "-j" });
+        if let Some(l) = options.instruction_limit {
+            cmd.arg("-t").arg(l.to_string());
+        }
+        if let Some(l) = options.memory_limit {
+            cmd.arg("-m").arg(l.to_string());
+        }
+        // read the script from stdin
+        cmd.arg(pool).arg("-").args(args);
+
+        let trace = CmdTrace::new("program", Some(pool));
+        let output = if options.read_only {
+            self.run_output_input(trace, cmd, Some(script.as_bytes()))?
+        } else {
+            // which datasets the program touches can't be known in advance, and listing every
+            // snapshot in the pool on each run costs too much, so none are recorded
+            self.run_audited_input(trace, cmd, None, Some(script.as_bytes()))?
+        };
+
+        program::parse_output(&String::from_utf8_lossy(&output.stdout)).map_err(|error| {
+            ZfsError::Parse {
+                cmd: "program",
+                error,
+            }
+        })
+    }
+
+    /// List the encryption state of `dataset` (and, if `recursive`, its descendants)
+    pub fn encryption_info(
+        &self,
+        dataset: &str,
+        recursive: bool,
+    ) -> Result<Vec<EncryptionInfo>, ZfsError> {
+        let mut builder = ListBuilder::default();
+        builder
+            .include_filesystems()
+            .include_volumes()
+            .with_elements(encryption::LIST_ELEMENTS)
+            .with_dataset(dataset);
+        if recursive {
+            builder.recursive();
+        }
+
+        let rows: Vec<Vec<String>> = From::from(&self.list_from_builder(&builder)?);
+        rows.iter()
+            .map(|row| EncryptionInfo::from_list_row(row))
+            .collect::<Result<_, _>>()
+            .map_err(|error| ZfsError::Parse { cmd: "list", error })
+    }
+
+    /// Load the key for the encryption root `dataset`
+    ///
+    /// With `LoadKeyFlags::DryRun`, this only verifies the key is correct, which is useful for
+    /// checking that an encrypted backup can be restored without making it accessible.
+    pub fn load_key(
+        &self,
+        flags: BitFlags<LoadKeyFlags>,
+        source: KeySource<'_>,
+        dataset: &str,
+    ) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("load-key");
+
+        if !flags.is_empty() {
+            let mut opts = "-".to_owned();
+            for flag in flags.iter() {
+                opts.push(match flag {
+                    LoadKeyFlags::Recursive => 'r',
+                    LoadKeyFlags::DryRun => 'n',
+                });
+            }
+
+            cmd.arg(opts);
+        }
+        let input = key_source_args(&mut cmd, source);
+        cmd.arg(dataset);
+
+        self.run_output_input(CmdTrace::new("load-key", Some(dataset)), cmd, input)?;
+        Ok(())
+    }
+
+    /// Unload the key for the encryption root `dataset`, making it (and any datasets using its
+    /// key) inaccessible. `dataset` must be unmounted.
+    pub fn unload_key(&self, recursive: bool, dataset: &str) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("unload-key");
+        if recursive {
+            cmd.arg("-r");
+        }
+        cmd.arg(dataset);
+
+        self.run_output(CmdTrace::new("unload-key", Some(dataset)), cmd)?;
+        Ok(())
+    }
+
+    /// Change the wrapping key of `dataset`, making it an encryption root.
+    ///
+    /// `props` may set `keylocation`, `keyformat`, and `pbkdf2iters`. The new key is read from
+    /// `source`. If `load` is set, the existing key is loaded first (`-l`).
+    pub fn change_key(
+        &self,
+        load: bool,
+        props: &[(&str, &str)],
+        source: KeySource<'_>,
+        dataset: &str,
+    ) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("change-key");
+        if load {
+            cmd.arg("-l");
+        }
+
+        let input = match source {
+            KeySource::Property => None,
+            KeySource::Location(l) => {
+                cmd.arg("-o").arg(format!("keylocation={}", l));
+                None
+            }
+            // `change-key` has no `-L`: the new key is read from stdin when `keylocation` is
+            // `prompt`
+            KeySource::Data(d) => {
+                cmd.arg("-o").arg("keylocation=prompt");
+                Some(d)
+            }
+        };
+        for prop in props.iter() {
+            cmd.arg("-o").arg(format!("{}={}", prop.0, prop.1));
+        }
         cmd.arg(dataset);
-        self.run_output(cmd)
+
+        self.run_audited_input(CmdTrace::new("change-key", Some(dataset)), cmd, None, input)?;
+        Ok(())
+    }
+
+    /// Make `dataset` inherit its wrapping key from its parent, so it is no longer an
+    /// encryption root (`zfs change-key -i`).
+    pub fn inherit_key(&self, load: bool, dataset: &str) -> Result<(), ZfsError> {
+        let mut cmd = self.cmd(OpClass::Mutate)?;
+        cmd.arg("change-key").arg("-i");
+        if load {
+            cmd.arg("-l");
+        }
+        cmd.arg(dataset);
+
+        self.run_audited(CmdTrace::new("change-key", Some(dataset)), cmd, None)?;
+        Ok(())
+    }
+
+    /// Stream the changes between the snapshot `from` and `to` (a later snapshot of the same
+    /// filesystem, or the filesystem itself for its current state)
+    pub fn diff(&self, from: &str, to: &str) -> Result<ZfsDiff, ZfsError> {
+        let mut cmd = self.cmd(OpClass::Read)?;
+        cmd.arg("diff").arg("-FHt").arg(from).arg(to);
+
+        let trace = CmdTrace::new("diff", Some(from));
+        info!(parent: &trace.span, "run: {:?}", cmd);
+
+        let mut child = cmd
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::piped())
+            .spawn()
+            .map_err(|e| ZfsError::Exec { io: e })?;
+
+        // read stderr as it is written, so `zfs diff` can't block on a full stderr pipe while we
+        // wait on stdout
+        let mut stderr_pipe = child.stderr.take().unwrap();
+        let stderr = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stderr_pipe.read_to_end(&mut buf);
+            buf
+        });
+
+        Ok(ZfsDiff {
+            stdout: io::BufReader::new(child.stdout.take().unwrap()),
+            child: Some(child),
+            stderr: Some(stderr),
+            cmd: format!("{:?}", cmd),
+            trace,
+        })
+    }
+
+    /// Wait for `activity` in the filesystem `dataset` to complete.
+    ///
+    /// If `timeout` elapses first, `zfs wait` is killed and `WaitStatus::TimedOut` is returned.
+    /// `zfs wait` reports no progress of its own, so `progress` is called about once a second
+    /// with the time waited so far.
+    pub fn wait<F: FnMut(Duration)>(
+        &self,
+        dataset: &str,
+        activity: DatasetActivity,
+        timeout: Option<Duration>,
+        mut progress: F,
+    ) -> Result<WaitStatus, ZfsError> {
+        let mut cmd = self.cmd(OpClass::Read)?;
+        cmd.arg("wait")
+            .arg("-t")
+            .arg(activity.as_str())
+            .arg(dataset);
+
+        let trace = CmdTrace::new("wait", Some(dataset));
+        info!(parent: &trace.span, "run: {:?}", cmd);
+
+        let mut child = cmd
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::piped())
+            .spawn()
+            .map_err(|e| ZfsError::Exec { io: e })?;
+
+        let poll = Duration::from_millis(100);
+        let mut last_progress = trace.start;
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(|e| ZfsError::Exec { io: e })? {
+                break status;
+            }
+
+            let elapsed = trace.start.elapsed();
+            if timeout.is_some_and(|t| elapsed >= t) {
+                // if it exits before the kill, it isn't an error
+                let _ = child.kill();
+                let status = child.wait().map_err(|e| ZfsError::Exec { io: e })?;
+                trace.finish(&status);
+                return Ok(WaitStatus::TimedOut);
+            }
+
+            if last_progress.elapsed() >= Duration::from_secs(1) {
+                last_progress = Instant::now();
+                progress(elapsed);
+            }
+
+            std::thread::sleep(poll);
+        };
+        trace.finish(&status);
+
+        if !status.success() {
+            let mut stderr = String::new();
+            let _ = child.stderr.take().unwrap().read_to_string(&mut stderr);
+            return Err(cmdinfo_to_error(CmdInfo {
+                status,
+                stderr,
+                cmd: format!("{:?}", cmd),
+            }));
+        }
+
+        Ok(WaitStatus::Complete)
     }
 
     // delete
@@ -478,7 +1490,7 @@ impl Zfs {
         receive_resume_token: &str,
         flags: BitFlags<SendFlags>,
     ) -> io::Result<ZfsSend> {
-        let mut cmd = self.cmd();
+        let mut cmd = self.cmd(OpClass::Read)?;
 
         cmd.arg("send");
 
@@ -513,19 +1525,21 @@ impl Zfs {
 
         cmd.arg("-t").arg(receive_resume_token);
 
-        info!("run: {:?}", cmd);
+        let trace = CmdTrace::new("send", None);
+        info!(parent: &trace.span, "run: {:?}", cmd);
 
         Ok(ZfsSend {
             child: cmd.stdout(std::process::Stdio::piped()).spawn()?,
+            trace,
         })
     }
 
     pub fn recv_abort_incomplete(&self, dataset: &str) -> Result<(), ZfsError> {
-        let mut cmd = self.cmd();
+        let mut cmd = self.cmd(OpClass::Mutate)?;
 
         cmd.arg("recv").arg("-A").arg(dataset);
 
-        self.run_output(cmd)?;
+        self.run_audited(CmdTrace::new("recv", Some(dataset)), cmd, None)?;
         Ok(())
     }
 
@@ -535,7 +1549,7 @@ impl Zfs {
         from: Option<&str>,
         flags: BitFlags<SendFlags>,
     ) -> io::Result<ZfsSend> {
-        let mut cmd = self.cmd();
+        let mut cmd = self.cmd(OpClass::Read)?;
 
         cmd.arg("send");
 
@@ -585,10 +1599,12 @@ impl Zfs {
 
         cmd.arg(snapname);
 
-        info!("run: {:?}", cmd);
+        let trace = CmdTrace::new("send", Some(snapname));
+        info!(parent: &trace.span, "run: {:?}", cmd);
 
         Ok(ZfsSend {
             child: cmd.stdout(std::process::Stdio::piped()).spawn()?,
+            trace,
         })
     }
 
@@ -607,7 +1623,7 @@ impl Zfs {
         exclude_props: &[&str],
         flags: BitFlags<RecvFlags>,
     ) -> io::Result<ZfsRecv> {
-        let mut cmd = self.cmd();
+        let mut cmd = self.cmd(OpClass::Mutate)?;
 
         cmd.arg("recv");
 
@@ -631,7 +1647,7 @@ impl Zfs {
             cmd.arg(opts);
         }
 
-        for set_prop in set_props.into_iter() {
+        for set_prop in set_props.iter() {
             let mut s = String::new();
             s.push_str(set_prop.0.as_ref());
             s.push('=');
@@ -639,34 +1655,133 @@ impl Zfs {
             cmd.arg("-o").arg(s);
         }
 
-        for exclude_prop in exclude_props.into_iter() {
+        for exclude_prop in exclude_props.iter() {
             cmd.arg("-x").arg(exclude_prop);
         }
 
-        match origin {
-            Some(o) => {
-                cmd.arg("-o").arg(o);
-            }
-            None => {}
+        if let Some(o) = origin {
+            cmd.arg("-o").arg(o);
         }
 
         cmd.arg(snapname);
-        info!("run: {:?}", cmd);
+
+        // a forced recv may destroy snapshots on the destination
+        let audit = if flags.contains(RecvFlags::DryRun) {
+            None
+        } else {
+            let affected = if flags.contains(RecvFlags::Force) {
+                Some((snapname, false))
+            } else {
+                None
+            };
+            self.audit_pending(&cmd, affected)?
+        };
+
+        let trace = CmdTrace::new("recv", Some(snapname));
+        info!(parent: &trace.span, "run: {:?}", cmd);
 
         Ok(ZfsRecv {
             child: cmd.stdin(std::process::Stdio::piped()).spawn()?,
+            trace,
+            audit,
         })
     }
 }
 
+/// Iterator over the records emitted by `zfs diff`, returned by `Zfs::diff()`
+///
+/// Once all records have been read, the exit status of `zfs diff` is checked and any failure is
+/// returned as a final error. If dropped before then, `zfs diff` is killed.
+pub struct ZfsDiff {
+    stdout: io::BufReader<process::ChildStdout>,
+    child: Option<process::Child>,
+    /// Collects stderr until `zfs diff` exits
+    stderr: Option<std::thread::JoinHandle<Vec<u8>>>,
+    cmd: String,
+    trace: CmdTrace,
+}
+
+impl ZfsDiff {
+    fn take_stderr(&mut self) -> Vec<u8> {
+        self.stderr
+            .take()
+            .and_then(|t| t.join().ok())
+            .unwrap_or_default()
+    }
+
+    fn finish(&mut self) -> Option<Result<DiffRecord, ZfsError>> {
+        let mut child = self.child.take()?;
+        let status = match child.wait() {
+            Ok(s) => s,
+            Err(e) => return Some(Err(ZfsError::Exec { io: e })),
+        };
+        let stderr = self.take_stderr();
+        self.trace.finish(&status);
+
+        if status.success() {
+            return None;
+        }
+
+        Some(Err(cmdinfo_to_error(CmdInfo {
+            status,
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            cmd: self.cmd.clone(),
+        })))
+    }
+
+    /// Don't leave `zfs` blocked writing to a pipe no one will read
+    fn kill(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.take_stderr();
+    }
+}
+
+impl Drop for ZfsDiff {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+impl Iterator for ZfsDiff {
+    type Item = Result<DiffRecord, ZfsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.child.as_ref()?;
+
+        let mut line = Vec::new();
+        match self.stdout.read_until(b'\n', &mut line) {
+            Ok(0) => self.finish(),
+            Ok(_) => {
+                if line.last() == Some(&b'\n') {
+                    line.pop();
+                }
+                Some(
+                    DiffRecord::parse_line(&line)
+                        .map_err(|error| ZfsError::Parse { cmd: "diff", error }),
+                )
+            }
+            Err(e) => {
+                self.kill();
+                Some(Err(ZfsError::Exec { io: e }))
+            }
+        }
+    }
+}
+
 pub struct ZfsSend {
     // note: in the lzc case, this is just a `fd`
     child: std::process::Child,
+    trace: CmdTrace,
 }
 
 pub struct ZfsRecv {
     // note: in the lzc case, this is just a `fd`
     child: std::process::Child,
+    trace: CmdTrace,
+    audit: Option<audit::Pending>,
 }
 
 pub fn send_recv(mut send: ZfsSend, mut recv: ZfsRecv) -> io::Result<u64> {
@@ -685,17 +1800,35 @@ pub fn send_recv(mut send: ZfsSend, mut recv: ZfsRecv) -> io::Result<u64> {
     let ss = send.child.wait()?;
     let rs = recv.child.wait()?;
 
+    send.trace.record_bytes(bytes);
+    send.trace.finish(&ss);
+    recv.trace.record_bytes(bytes);
+    recv.trace.finish(&rs);
+
+    if let Some(audit) = recv.audit.take() {
+        audit.finish(if rs.success() {
+            audit::Outcome::Success
+        } else {
+            audit::Outcome::Failed {
+                error: format!("recv failed: {:?}", rs.code()),
+            }
+        });
+    }
+
     if !ss.success() || !rs.success() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("send or recv failed: {:?}, {:?}", ss.code(), rs.code()),
-        ));
+        return Err(io::Error::other(format!(
+            "send or recv failed: {:?}, {:?}",
+            ss.code(),
+            rs.code()
+        )));
     }
 
     Ok(bytes)
 }
 
-#[derive(BitFlags, Copy, Clone, Debug, PartialEq, Eq)]
+#[bitflags]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum RecvFlags {
     // correspond to `lzc` booleans/functions
     /// -F
@@ -732,7 +1865,9 @@ pub enum RecvFlags {
     DryRun = 1 << 7,
 }
 
-#[derive(BitFlags, Copy, Clone, Debug, PartialEq, Eq)]
+#[bitflags]
+#[repr(u16)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SendFlags {
     // correspond to lzc SendFlags
     /// -e
@@ -763,7 +1898,9 @@ pub enum SendFlags {
     Replicate = 1 << 11,
 }
 
-#[derive(BitFlags, Copy, Clone, Debug, PartialEq, Eq)]
+#[bitflags]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DestroyFlags {
     RecursiveDependents = 1 << 0,
     ForceUmount = 1 << 1,
@@ -771,6 +1908,44 @@ pub enum DestroyFlags {
     MachineParsable = 1 << 3,
     RecursiveChildren = 1 << 4,
     Verbose = 1 << 5,
+    /// -d: mark held or cloned snapshots for deferred destruction instead of failing
+    Defer = 1 << 6,
+}
+
+#[bitflags]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RollbackFlags {
+    /// -r: destroy snapshots and bookmarks more recent than the target
+    DestroyLater = 1 << 0,
+    /// -R: like `-r`, and also destroy clones of the destroyed snapshots
+    DestroyLaterAndClones = 1 << 1,
+    /// -f
+    ForceUmount = 1 << 2,
+}
+
+#[bitflags]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CreateFlags {
+    /// -p: create missing parent datasets. Succeeds if `dataset` already exists.
+    CreateParents = 1 << 0,
+    /// -u: do not mount the new file system
+    NoMount = 1 << 1,
+}
+
+#[bitflags]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenameFlags {
+    /// -r: rename snapshots of all descendant datasets (snapshots only)
+    Recursive = 1 << 0,
+    /// -f
+    ForceUmount = 1 << 1,
+    /// -p
+    CreateParents = 1 << 2,
+    /// -u: do not remount file systems during rename
+    NoRemount = 1 << 3,
 }
 
 //
@@ -804,6 +1979,97 @@ pub enum DestroyFlags {
 //  generate stream from the first <snapshot> [src] to the second <snapshot> [target]
 //
 
+impl Zfs {
+    /// Configure from environment variables named with `prefix`, allowing (for example) the
+    /// source and destination of a transfer to be configured separately.
+    ///
+    ///  - `<prefix>_ZFS_CMD`: whitespace separated command used to run `zfs` (ie: `ssh host zfs`).
+    ///    If unset, falls back to the same behavior as `Default`.
+    ///  - `<prefix>_ZFS_READ_PREFIX`, `<prefix>_ZFS_MUTATE_PREFIX`: whitespace separated
+    ///    commands placed before the final word of `<prefix>_ZFS_CMD` for each `OpClass` (ie:
+    ///    `sudo -n`).
+    ///  - `<prefix>_ZFS_AUDIT_LOG`: path of a file to append a record of each state changing
+    ///    operation to.
+    pub fn from_env_prefix(prefix: &str) -> Self {
+        fn env_words(name: String) -> Option<Vec<String>> {
+            let v = env::var(name).ok()?;
+            Some(v.split_whitespace().map(|s| s.to_owned()).collect())
+        }
+
+        let mut zfs = Zfs::default();
+
+        if let Some(cmd) = env_words(format!("{}_ZFS_CMD", prefix)) {
+            if !cmd.is_empty() {
+                zfs.zfs_cmd = cmd;
+            }
+        }
+
+        if let Some(p) = env_words(format!("{}_ZFS_READ_PREFIX", prefix)) {
+            zfs.prefix(OpClass::Read, p);
+        }
+
+        if let Some(p) = env_words(format!("{}_ZFS_MUTATE_PREFIX", prefix)) {
+            zfs.prefix(OpClass::Mutate, p);
+        }
+
+        if let Some(path) = env::var_os(format!("{}_ZFS_AUDIT_LOG", prefix)) {
+            zfs.audit_log(path);
+        }
+
+        zfs
+    }
+
+    /// Append a record of each state changing operation (destroy, recv, rollback, set, rename)
+    /// to the file at `path`.
+    pub fn audit_log<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.audit_log = Some(path.into());
+        self
+    }
+
+    /// Run operations of `class` with `prefix` placed before the `zfs` command. The prefix goes
+    /// before the final word of the command, so with `ssh host zfs` it is run on `host`.
+    ///
+    /// The prefix is typically a privilege escalation tool (`sudo`, `doas`), allowing the
+    /// sudoers rules to only permit the specific operations that need privileges.
+    pub fn prefix(&mut self, class: OpClass, prefix: Vec<String>) -> &mut Self {
+        match class {
+            OpClass::Read => self.read_prefix = prefix,
+            OpClass::Mutate => self.mutate_prefix = prefix,
+        }
+        self
+    }
+
+    /// A command which runs `program` on the host `zfs` runs on, ie: after the words which
+    /// precede `zfs` in the zfs command (like `ssh host`). Neither prefix is applied.
+    pub fn host_cmd(&self, program: &str) -> process::Command {
+        let before = self
+            .zfs_cmd
+            .split_last()
+            .map_or(&[][..], |(_, before)| before);
+        match before.split_first() {
+            Some((first, rest)) => {
+                let mut cmd = process::Command::new(first);
+                cmd.args(rest).arg(program);
+                cmd
+            }
+            None => process::Command::new(program),
+        }
+    }
+
+    /// Whether `zfs` is run on another host, ie: the zfs command starts with `ssh` (or `rsh`).
+    /// Other words before `zfs` (like `sudo` or `env`) still run it locally.
+    pub fn is_remote(&self) -> bool {
+        let (_, before) = match self.zfs_cmd.split_last() {
+            Some(v) => v,
+            None => return false,
+        };
+        before
+            .first()
+            .and_then(|program| Path::new(program).file_name())
+            .is_some_and(|name| name == "ssh" || name == "rsh")
+    }
+}
+
 impl Default for Zfs {
     fn default() -> Self {
         Zfs {
@@ -812,6 +2078,9 @@ impl Default for Zfs {
                 .to_str()
                 .unwrap()
                 .to_owned()],
+            read_prefix: Vec::new(),
+            mutate_prefix: Vec::new(),
+            audit_log: None,
         }
     }
 }
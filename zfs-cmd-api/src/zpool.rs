@@ -1,13 +1,27 @@
 #![allow(dead_code)]
 
-use super::{Error, Pool};
-use camino::Utf8PathBuf as PathBuf;
+use super::events::{EventParser, ZpoolEvent};
+use super::history::{self, HistoryRecord};
+use super::iostat::{IostatOptions, IostatParser, IostatRow};
+use super::maintenance::{CheckpointAction, InitializeAction, ScrubAction, TrimAction};
+use super::status::{self, ImportablePool, PoolStatus, VdevState};
+use super::value::{self, FeatureState, PropertyValue};
+use super::vdev::{self, Layout, VdevSpec};
+use super::wait::{self, PoolActivity, WaitStatus};
+use super::{Error, ParseError, PoolName};
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
 use eyre::{eyre, WrapErr};
 use serde_derive::Deserialize;
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::BTreeMap,
     env,
+    process::{Output, Stdio},
+    time::{Duration, Instant, SystemTime},
 };
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::task::JoinHandle;
+use tracing::{field, info, info_span, Instrument, Span};
 
 #[derive(Debug)]
 pub struct ZpoolCmd {
@@ -25,17 +39,18 @@ pub struct ZpoolCmd {
   "pools": {
     "mainrust": {
 */
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
 pub struct ZpoolList {
-    output_version: ZpoolListOutputVersion,
-    pools: Vec<ZpoolListPool>,
+    pub output_version: ZpoolListOutputVersion,
+    /// Keyed by pool name
+    pub pools: BTreeMap<String, ZpoolListPool>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ZpoolListOutputVersion {
-    command: String,
-    vers_major: u32,
-    vers_minor: u32,
+    pub command: String,
+    pub vers_major: u32,
+    pub vers_minor: u32,
 }
 
 /*
@@ -50,131 +65,1142 @@ pub struct ZpoolListOutputVersion {
       "spa_version": "5000",
       "zpl_version": "5",
 */
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
 pub struct ZpoolListPool {
-    name: String,
-    r#type: String,
-    state: String,
-    pool_guid: String,
-    txg: String,
-    spa_version: String,
-    zpl_version: String,
-    properties: BTreeMap<String, ZpoolListPoolProperty>,
-    vdev: BTreeMap<String, ZpoolListVdev>,
+    pub name: String,
+    pub r#type: String,
+    pub state: String,
+    pub pool_guid: String,
+    pub txg: String,
+    pub spa_version: String,
+    pub zpl_version: String,
+    pub properties: BTreeMap<String, ZpoolListProperty>,
+    /// Top level vdevs (only with `-v`), keyed by name
+    #[serde(default)]
+    pub vdevs: BTreeMap<String, ZpoolListVdev>,
+}
+
+impl ZpoolListPool {
+    /// The properties `zpool list` reports, parsed
+    pub fn typed_properties(&self) -> Result<PoolProperties, ParseError> {
+        PoolProperties::from_map(&self.properties)
+    }
+}
+
+/// Commonly used pool properties. Each is `None` if not reported (or `-`).
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PoolProperties {
+    /// Bytes
+    pub size: Option<u64>,
+    pub allocated: Option<u64>,
+    pub free: Option<u64>,
+    /// Percentage of free space which is fragmented
+    pub fragmentation: Option<f64>,
+    /// Percentage of `size` allocated
+    pub capacity: Option<f64>,
+    pub dedupratio: Option<f64>,
+    pub health: Option<VdevState>,
+    pub altroot: Option<String>,
+    /// Bytes used by the checkpoint, if there is one
+    pub checkpoint: Option<u64>,
 }
 
+impl PoolProperties {
+    /// Parse the properties in `properties` (from `zpool list` or `zpool get`)
+    pub fn from_map(properties: &BTreeMap<String, ZpoolListProperty>) -> Result<Self, ParseError> {
+        let get = |k: &str| properties.get(k).filter(|p| p.value != "-");
+        let bytes = |k: &str| get(k).map(|p| p.bytes()).transpose().map(Option::flatten);
+        let percent = |k: &str| get(k).map(|p| p.percent()).transpose().map(Option::flatten);
+
+        Ok(PoolProperties {
+            size: bytes("size")?,
+            allocated: bytes("allocated")?,
+            free: bytes("free")?,
+            fragmentation: percent("fragmentation")?,
+            capacity: percent("capacity")?,
+            dedupratio: get("dedupratio").map(|p| p.ratio()).transpose()?.flatten(),
+            health: get("health").map(|p| VdevState::parse(&p.value)),
+            altroot: get("altroot").map(|p| p.value.clone()),
+            checkpoint: bytes("checkpoint")?,
+        })
+    }
+}
+
+/*
+ *
+      "vdevs": {
+        "mirror-0": {
+          "name": "mirror-0",
+          "vdev_type": "mirror",
+          "guid": "15289479982256741534",
+          "class": "normal",
+          "state": "ONLINE",
+          "properties": { ... },
+          "vdevs": {
+            "nvme-eui.000000000000000100a0752340ea6c2b-part3": {
+              "name": "nvme-eui.000000000000000100a0752340ea6c2b-part3",
+              "vdev_type": "disk",
+              "guid": "2213468376004524853",
+              "path": "/dev/disk/by-id/nvme-eui.000000000000000100a0752340ea6c2b-part3",
+*/
 #[derive(Debug, Deserialize)]
 pub struct ZpoolListVdev {
-    name: String,
-    vdev_type: String,
-    guid: String,
-    class: String,
-    state: String,
-    properties: BTreeMap<String, ZpoolListVdevProperty>,
+    pub name: String,
+    /// `disk`, `file`, `mirror`, `raidz`, `replacing`, `spare`, etc
+    pub vdev_type: String,
+    pub guid: String,
+    /// Only present for leaf vdevs
+    pub path: Option<String>,
+    /// Only present for (some) leaf vdevs
+    pub devid: Option<String>,
+    pub phys_path: Option<String>,
+    /// `normal`, `special`, `dedup`, `log`, etc
+    pub class: String,
+    pub state: String,
+    #[serde(default)]
+    pub properties: BTreeMap<String, ZpoolListProperty>,
+    /// Children, keyed by name
+    #[serde(default)]
+    pub vdevs: BTreeMap<String, ZpoolListVdev>,
+}
+
+impl ZpoolListVdev {
+    /// This vdev and all of its descendants, parents before their children
+    pub fn walk(&self) -> Vec<&ZpoolListVdev> {
+        let mut v = vec![self];
+        for child in self.vdevs.values() {
+            v.extend(child.walk());
+        }
+        v
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.vdevs.is_empty()
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct ZpoolListProperty {
+    pub value: String,
+    pub source: ZpoolListPropertySource,
+}
+
+impl ZpoolListProperty {
+    /// `value` as a size in bytes (ie: `size`, `allocated`, `free`)
+    pub fn bytes(&self) -> Result<Option<u64>, ParseError> {
+        value::parse_size(&self.value)
+    }
+
+    /// `value` as a percentage (ie: `capacity`, `fragmentation`)
+    pub fn percent(&self) -> Result<Option<f64>, ParseError> {
+        value::parse_percent(&self.value)
+    }
+
+    /// `value` as a ratio (ie: `dedupratio`)
+    pub fn ratio(&self) -> Result<Option<f64>, ParseError> {
+        value::parse_ratio(&self.value)
+    }
+
+    /// `value` as a boolean (ie: `autoexpand`)
+    pub fn bool(&self) -> Result<Option<bool>, ParseError> {
+        value::parse_bool(&self.value)
+    }
+
+    /// `value` as a feature state (`feature@*` properties)
+    pub fn feature(&self) -> Result<Option<FeatureState>, ParseError> {
+        value::parse_feature(&self.value)
+    }
+
+    /// `value` with its type inferred from its form
+    pub fn typed(&self) -> PropertyValue {
+        PropertyValue::parse(&self.value)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct ZpoolListPropertySource {
+    /// `NONE`, `DEFAULT`, `LOCAL`, etc
+    pub r#type: String,
+    pub data: String,
 }
 
+/// Output of `zpool get -j`
 #[derive(Debug, Deserialize)]
-pub struct ZpoolListVdevProperty {
-    name: String,
-    value: String,
-    source: ZpoolListVdevPropertySource,
+struct ZpoolGet {
+    pools: BTreeMap<String, ZpoolGetPool>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct ZpoolListVdevPropertySource {
-    r#type: String,
-    data: String,
+struct ZpoolGetPool {
+    properties: BTreeMap<String, ZpoolListProperty>,
+}
+
+/// Parse the output of `zpool get -Hp -o property,value,source`
+///
+/// Sources are given in the form `zpool get -j` uses: `NONE` (for `-`), `DEFAULT`, `LOCAL`.
+pub fn parse_get(output: &str) -> Result<BTreeMap<String, ZpoolListProperty>, ParseError> {
+    let mut properties = BTreeMap::new();
+    for line in output.lines().filter(|l| !l.is_empty()) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let (property, value, source) = match fields[..] {
+            [p, v, s] => (p, v, s),
+            _ => return Err(ParseError::new(line, "expected 3 fields")),
+        };
+
+        let source_type = match source {
+            "-" => "NONE".to_owned(),
+            s => s.to_uppercase(),
+        };
+        properties.insert(
+            property.to_owned(),
+            ZpoolListProperty {
+                value: value.to_owned(),
+                source: ZpoolListPropertySource {
+                    r#type: source_type,
+                    data: "-".to_owned(),
+                },
+            },
+        );
+    }
+    Ok(properties)
 }
 
 impl ZpoolCmd {
-    pub async fn list_pools(&self) -> Result<Vec<Pool>, Error> {
-        let output = tokio::process::Command::new(&self.zpool_cmd)
-            .arg("list")
-            .arg("-H")
-            .arg("-o")
-            .arg("name")
+    /// Run `zpool_cmd` as `zpool`, instead of `$ZPOOL_CMD` (or `zpool`)
+    pub fn new<P: Into<PathBuf>>(zpool_cmd: P) -> Self {
+        ZpoolCmd {
+            zpool_cmd: zpool_cmd.into(),
+        }
+    }
+
+    fn cmd(&self) -> Command {
+        Command::new(&self.zpool_cmd)
+    }
+
+    /// Run `cmd` to completion, returning its output regardless of exit status.
+    ///
+    /// Each execution is traced in a `zpool` span carrying the subcommand and pool. The duration
+    /// and exit status are recorded on the span once the command completes.
+    async fn run(
+        &self,
+        subcommand: &'static str,
+        pool: Option<&str>,
+        mut cmd: Command,
+    ) -> Result<Output, Error> {
+        let span = info_span!(
+            "zpool",
+            subcommand,
+            pool,
+            duration_ms = field::Empty,
+            status = field::Empty,
+        );
+        info!(parent: &span, "run: {:?}", cmd);
+
+        let start = Instant::now();
+        let output = cmd
             .output()
+            .instrument(span.clone())
             .await
-            .wrap_err("Failed to execute zpool list")?;
+            .wrap_err_with(|| format!("Failed to execute zpool {}", subcommand))?;
+
+        span.record("duration_ms", start.elapsed().as_millis() as u64);
+        span.record("status", output.status.code());
+
+        Ok(output)
+    }
+
+    /// Run `cmd` to completion, returning its output if it exited successfully.
+    async fn run_output(
+        &self,
+        subcommand: &'static str,
+        pool: Option<&str>,
+        cmd: Command,
+    ) -> Result<Output, Error> {
+        let output = self.run(subcommand, pool, cmd).await?;
 
         if output.status.success() {
-            Ok(String::from_utf8(output.stdout)
-                .wrap_err("Failed to parse zpool list output")?
-                .trim_end()
-                .lines()
-                .map(|line| Pool(line.to_owned()))
-                .collect())
+            Ok(output)
         } else {
             Err(eyre!(
-                "zpool list failed: {}",
+                "zpool {} failed: {}",
+                subcommand,
                 String::from_utf8_lossy(&output.stderr)
             )
             .into())
         }
     }
 
-    pub async fn list_pool(&self, pool: &str) -> Result<Pool, Error> {
-        let output = tokio::process::Command::new(&self.zpool_cmd)
-            .arg("list")
-            .arg("-H")
-            .arg("-o")
-            .arg("name")
-            .arg(pool)
-            .output()
-            .await
-            .wrap_err("Failed to execute zpool list")?;
+    pub async fn list_pools(&self) -> Result<Vec<PoolName>, Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("list").arg("-H").arg("-o").arg("name");
+
+        let output = self.run_output("list", None, cmd).await?;
+
+        Ok(String::from_utf8(output.stdout)
+            .wrap_err("Failed to parse zpool list output")?
+            .trim_end()
+            .lines()
+            .map(|line| PoolName(line.to_owned()))
+            .collect())
+    }
+
+    pub async fn list_pool(&self, pool: &str) -> Result<PoolName, Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("list").arg("-H").arg("-o").arg("name").arg(pool);
+
+        let output = self.run_output("list", Some(pool), cmd).await?;
+
+        Ok(String::from_utf8(output.stdout)
+            .wrap_err("Failed to parse zpool list output")?
+            .trim_end()
+            .lines()
+            .map(|line| PoolName(line.to_owned()))
+            .next()
+            .ok_or_else(|| eyre!("Pool not found"))?)
+    }
 
+    /// Health, scan progress, and errors of `pool` (or, with `None`, all pools), including the
+    /// list of files with permanent errors.
+    ///
+    /// Uses `zpool status -j` where supported, falling back to parsing the text output.
+    pub async fn status(&self, pool: Option<&str>) -> Result<Vec<PoolStatus>, Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("status").arg("-jpv").args(pool);
+
+        let output = self.run("status", pool, cmd).await?;
         if output.status.success() {
-            Ok(String::from_utf8(output.stdout)
-                .wrap_err("Failed to parse zpool list output")?
-                .trim_end()
-                .lines()
-                .map(|line| Pool(line.to_owned()))
-                .next()
-                .ok_or_else(|| eyre!("Pool not found"))?)
+            return Ok(status::parse_json(&output.stdout, SystemTime::now())
+                .wrap_err("Failed to parse zpool status output")?);
+        }
+
+        // versions without json support reject `-j` as an invalid option
+        if !String::from_utf8_lossy(&output.stderr).contains("invalid option") {
+            return Err(eyre!(
+                "zpool status failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )
+            .into());
+        }
+
+        // scan times are printed in local time
+        let mut cmd = self.cmd();
+        cmd.arg("status").arg("-pv").args(pool).env("TZ", "UTC");
+
+        let output = self.run_output("status", pool, cmd).await?;
+        Ok(status::parse_text(&String::from_utf8_lossy(&output.stdout))
+            .wrap_err("Failed to parse zpool status output")?)
+    }
+
+    /// Exact values of `properties` (or, if empty, all properties) of `pool`, keyed by name
+    ///
+    /// Uses `zpool get -j` where supported, falling back to parsing the text output.
+    pub async fn get(
+        &self,
+        pool: &str,
+        properties: &[&str],
+    ) -> Result<BTreeMap<String, ZpoolListProperty>, Error> {
+        let properties = if properties.is_empty() {
+            "all".to_owned()
         } else {
-            Err(eyre!(
-                "zpool list failed: {}",
+            properties.join(",")
+        };
+
+        let mut cmd = self.cmd();
+        cmd.arg("get").arg("-jp").arg(&properties).arg(pool);
+
+        let output = self.run("get", Some(pool), cmd).await?;
+        if output.status.success() {
+            let mut get: ZpoolGet = serde_json::from_slice(&output.stdout)
+                .wrap_err("Failed to parse zpool get output")?;
+            return Ok(get
+                .pools
+                .remove(pool)
+                .map(|p| p.properties)
+                .unwrap_or_default());
+        }
+
+        // versions without json support reject `-j` as an invalid option
+        if !String::from_utf8_lossy(&output.stderr).contains("invalid option") {
+            return Err(eyre!(
+                "zpool get failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             )
-            .into())
+            .into());
         }
+
+        let mut cmd = self.cmd();
+        cmd.arg("get")
+            .arg("-Hp")
+            .arg("-o")
+            .arg("property,value,source")
+            .arg(&properties)
+            .arg(pool);
+
+        let output = self.run_output("get", Some(pool), cmd).await?;
+        Ok(parse_get(&String::from_utf8_lossy(&output.stdout))
+            .wrap_err("Failed to parse zpool get output")?)
     }
 
-    // -g   Display vdev, GUIDs
-    // -L   Display real paths of vdevs resolving all symbolic links
-    // -n   Display config that would be used without adding
-    // -P   Display real paths of vdevs instead of last component
-    pub async fn add(&self, _force: bool, _pool: &str, _vdev: &str) -> Result<(), ()> {
-        unimplemented!();
+    /// State of each feature of `pool`, keyed by name (without the `feature@` prefix)
+    pub async fn features(&self, pool: &str) -> Result<BTreeMap<String, FeatureState>, Error> {
+        let mut features = BTreeMap::new();
+        for (name, p) in self.get(pool, &[]).await? {
+            if let Some(feature) = name.strip_prefix("feature@") {
+                if let Some(state) = p.feature().wrap_err("Failed to parse feature state")? {
+                    features.insert(feature.to_owned(), state);
+                }
+            }
+        }
+        Ok(features)
     }
 
+    /// Set `property` on `pool`
+    pub async fn set(&self, pool: &str, property: &PoolProperty) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("set")
+            .arg(format!("{}={}", property.property, property.value))
+            .arg(pool);
+
+        self.run_output("set", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Commands run against `pool`, and the internal events and ioctls they caused
+    /// (`zpool history -il`), oldest first
+    pub async fn history(&self, pool: &str) -> Result<Vec<HistoryRecord>, Error> {
+        let mut cmd = self.cmd();
+        // times are printed in local time, without a zone
+        cmd.env("TZ", "UTC").arg("history").arg("-il").arg(pool);
+
+        let output = self.run_output("history", Some(pool), cmd).await?;
+        Ok(
+            history::parse_history(&String::from_utf8_lossy(&output.stdout))
+                .wrap_err("Failed to parse zpool history output")?,
+        )
+    }
+
+    /// Wait for `activities` (or, if empty, all activities) on `pool` to complete.
+    ///
+    /// If `timeout` elapses first, `zpool wait` is killed and `WaitStatus::TimedOut` is returned.
+    /// If `interval` is given, `progress` is called that often with the bytes remaining for each
+    /// activity.
+    pub async fn wait<F>(
+        &self,
+        pool: &str,
+        activities: &[PoolActivity],
+        timeout: Option<Duration>,
+        interval: Option<Duration>,
+        mut progress: F,
+    ) -> Result<WaitStatus, Error>
+    where
+        F: FnMut(&[(PoolActivity, u64)]),
+    {
+        let mut cmd = self.cmd();
+        cmd.arg("wait").arg("-Hp");
+        if !activities.is_empty() {
+            let names: Vec<&str> = activities.iter().map(|a| a.as_str()).collect();
+            cmd.arg("-t").arg(names.join(","));
+        }
+        cmd.arg(pool);
+        if let Some(interval) = interval {
+            cmd.arg(format!("{}", interval.as_secs_f64()));
+        }
+
+        let span = info_span!(
+            "zpool",
+            subcommand = "wait",
+            pool,
+            duration_ms = field::Empty,
+            status = field::Empty,
+        );
+        info!(parent: &span, "run: {:?}", cmd);
+
+        let start = Instant::now();
+        let mut child = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err("Failed to execute zpool wait")?;
+        let stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+
+        let columns = wait::progress_columns(activities);
+        let follow = async {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Some(line) = lines
+                .next_line()
+                .await
+                .wrap_err("Failed to read zpool wait output")?
+            {
+                progress(&wait::parse_progress(&line, &columns)?);
+            }
+
+            Ok::<_, eyre::Report>(())
+        };
+
+        let followed = match timeout {
+            Some(t) => tokio::time::timeout(t, follow)
+                .instrument(span.clone())
+                .await
+                .ok(),
+            None => Some(follow.instrument(span.clone()).await),
+        };
+
+        if !matches!(followed, Some(Ok(()))) {
+            // timed out, or we couldn't follow the output. if it exits before the kill, it isn't
+            // an error
+            let _ = child.kill().await;
+        }
+        let status = child
+            .wait()
+            .await
+            .wrap_err("Failed to wait for zpool wait")?;
+
+        span.record("duration_ms", start.elapsed().as_millis() as u64);
+        span.record("status", status.code());
+
+        match followed {
+            None => Ok(WaitStatus::TimedOut),
+            Some(Err(e)) => Err(e.into()),
+            Some(Ok(())) if status.success() => Ok(WaitStatus::Complete),
+            Some(Ok(())) => {
+                let mut msg = Vec::new();
+                let _ = stderr.read_to_end(&mut msg).await;
+                Err(eyre!("zpool wait failed: {}", String::from_utf8_lossy(&msg)).into())
+            }
+        }
+    }
+
+    /// Add the vdevs described by `vdevs` to `pool`.
+    ///
+    /// `force` (`-f`) permits adding vdevs with a different replication level than the pool's
+    /// existing vdevs, or devices which appear to be in use.
+    pub async fn add(&self, force: bool, pool: &str, vdevs: &[VdevSpec]) -> Result<(), Error> {
+        let cmd = self.add_cmd(force, false, pool, vdevs)?;
+        self.run_output("add", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Report the layout `pool` would have if `vdevs` were added, without adding them (`-n`).
+    pub async fn add_dry_run(
+        &self,
+        force: bool,
+        pool: &str,
+        vdevs: &[VdevSpec],
+    ) -> Result<Layout, Error> {
+        let cmd = self.add_cmd(force, true, pool, vdevs)?;
+        let output = self.run_output("add", Some(pool), cmd).await?;
+
+        Ok(
+            vdev::parse_dry_run(&String::from_utf8_lossy(&output.stdout))
+                .wrap_err("Failed to parse zpool add output")?,
+        )
+    }
+
+    fn add_cmd(
+        &self,
+        force: bool,
+        dry_run: bool,
+        pool: &str,
+        vdevs: &[VdevSpec],
+    ) -> Result<Command, Error> {
+        if vdevs.is_empty() {
+            return Err(eyre!("no vdevs to add").into());
+        }
+        for v in vdevs.iter() {
+            v.validate()
+                .map_err(|e| eyre!("invalid vdev {:?}: {}", v, e))?;
+        }
+
+        let mut cmd = self.cmd();
+        cmd.arg("add");
+        if force {
+            cmd.arg("-f");
+        }
+        if dry_run {
+            cmd.arg("-n");
+        }
+        cmd.arg(pool);
+        for v in vdevs.iter() {
+            cmd.args(v.args());
+        }
+
+        Ok(cmd)
+    }
+
+    /// Attach `new_device` to `device` in `pool`, forming (or extending) a mirror and starting a
+    /// resilver. If `device` is part of a raidz vdev, this expands the raidz instead.
+    ///
+    /// `pool_properties` may only include `ashift`.
     pub async fn attach(
         &self,
-        _force: bool,
-        _pool_properties: Vec<PoolProperty>,
-        _pool: &str,
-        _device: &str,
-        _new_device: &str,
-    ) -> Result<(), ()> {
-        unimplemented!();
+        force: bool,
+        pool_properties: &[PoolProperty],
+        pool: &str,
+        device: &str,
+        new_device: &str,
+    ) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("attach");
+        if force {
+            cmd.arg("-f");
+        }
+        for p in pool_properties.iter() {
+            cmd.arg("-o").arg(format!("{}={}", p.property, p.value));
+        }
+        cmd.arg(pool).arg(device).arg(new_device);
+
+        self.run_output("attach", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Clear device errors in `pool`, or only those of `device`
+    pub async fn clear(&self, pool: &str, device: Option<&str>) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("clear").arg(pool).args(device);
+
+        self.run_output("clear", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Start, pause, or stop a scrub of `pool`. With `error_scrub` (`-e`), act on an error scrub,
+    /// which only verifies blocks with known errors.
+    ///
+    /// Returns once the scrub starts; use `wait()` to wait for it to complete.
+    pub async fn scrub(
+        &self,
+        pool: &str,
+        action: ScrubAction,
+        error_scrub: bool,
+    ) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("scrub").args(action.args(error_scrub)).arg(pool);
+
+        self.run_output("scrub", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Start, cancel, or suspend trimming `devices` of `pool` (or, if empty, all of its devices)
+    pub async fn trim(
+        &self,
+        pool: &str,
+        action: TrimAction,
+        devices: &[&str],
+    ) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("trim").args(action.args()).arg(pool).args(devices);
+
+        self.run_output("trim", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Start, cancel, suspend, or clear initializing `devices` of `pool` (or, if empty, all of its
+    /// devices)
+    pub async fn initialize(
+        &self,
+        pool: &str,
+        action: InitializeAction,
+        devices: &[&str],
+    ) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("initialize")
+            .args(action.args())
+            .arg(pool)
+            .args(devices);
+
+        self.run_output("initialize", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Create or discard the checkpoint of `pool`.
+    ///
+    /// While a checkpoint exists, space freed in the pool is not reclaimed, and vdevs can't be
+    /// removed, attached, or expanded. Rewinding to it requires exporting the pool and importing
+    /// it with `ImportOptions::rewind_to_checkpoint`.
+    pub async fn checkpoint(&self, pool: &str, action: CheckpointAction) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("checkpoint").args(action.args()).arg(pool);
+
+        self.run_output("checkpoint", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Bytes held by the checkpoint of `pool`, or `None` if it has no checkpoint
+    pub async fn checkpoint_size(&self, pool: &str) -> Result<Option<u64>, Error> {
+        let properties = self.get(pool, &["checkpoint"]).await?;
+        let properties = PoolProperties::from_map(&properties)
+            .wrap_err("Failed to parse checkpoint property")?;
+        Ok(properties.checkpoint.filter(|&size| size != 0))
+    }
+
+    /// List all pools along with their vdev trees (`zpool list -jv`)
+    pub async fn list(&self) -> Result<ZpoolList, Error> {
+        self.list_json(false, None).await
+    }
+
+    /// Like `list()`, but with exact (`-p`) property values, and optionally for only `pool`
+    pub async fn list_parsable(&self, pool: Option<&str>) -> Result<ZpoolList, Error> {
+        self.list_json(true, pool).await
+    }
+
+    async fn list_json(&self, parsable: bool, pool: Option<&str>) -> Result<ZpoolList, Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("list")
+            .arg(if parsable { "-jvp" } else { "-jv" })
+            .args(pool);
+
+        let output = self.run_output("list", pool, cmd).await?;
+
+        Ok(serde_json::from_slice(&output.stdout).wrap_err("Failed to parse zpool list output")?)
+    }
+
+    /// Pools available for import from devices in `dirs` (or, if empty, the default device
+    /// directories). With `destroyed` (`-D`), list only destroyed pools.
+    pub async fn import_discover(
+        &self,
+        dirs: &[&str],
+        destroyed: bool,
+    ) -> Result<Vec<ImportablePool>, Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("import");
+        for dir in dirs.iter() {
+            cmd.arg("-d").arg(dir);
+        }
+        if destroyed {
+            cmd.arg("-D");
+        }
+
+        let output = self.run("import", None, cmd).await?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            if stderr.contains("no pools available") {
+                return Ok(Vec::new());
+            }
+            return Err(eyre!("zpool import failed: {}", stderr).into());
+        }
+
+        Ok(
+            status::parse_import(&String::from_utf8_lossy(&output.stdout))
+                .wrap_err("Failed to parse zpool import output")?,
+        )
+    }
+
+    /// Import the pool named `pool` (or with guid `pool`), optionally renaming it to `new_name`
+    pub async fn import(
+        &self,
+        pool: &str,
+        new_name: Option<&str>,
+        options: &ImportOptions,
+    ) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("import")
+            .args(options.args())
+            .arg(pool)
+            .args(new_name);
+
+        self.run_output("import", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Export `pool`, unmounting its filesystems. With `force` (`-f`), unmount them even if busy.
+    pub async fn export(&self, pool: &str, force: bool) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("export");
+        if force {
+            cmd.arg("-f");
+        }
+        cmd.arg(pool);
+
+        self.run_output("export", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Start `zpool iostat -v`, reporting statistics of `pools` (or, if empty, all pools) and
+    /// their vdevs every `interval`, stopping after `count` intervals if given.
+    ///
+    /// The first interval covers the time since each pool was imported.
+    pub async fn iostat(
+        &self,
+        pools: &[&str],
+        options: &IostatOptions,
+        interval: Duration,
+        count: Option<u64>,
+    ) -> Result<Iostat, Error> {
+        // named explicitly, so pools imported or exported meanwhile can't make the parser's list
+        // and `zpool iostat`'s disagree
+        let names: Vec<String> = if pools.is_empty() {
+            self.list_pools().await?.into_iter().map(|p| p.0).collect()
+        } else {
+            pools.iter().map(|p| (*p).to_owned()).collect()
+        };
+
+        let mut cmd = self.cmd();
+        cmd.arg("iostat")
+            .arg("-Hpv")
+            .args(options.args().map_err(|e| eyre!(e))?)
+            .args(&names)
+            .arg(format!("{}", interval.as_secs_f64()))
+            .args(count.map(|c| c.to_string()));
+
+        let pool = if pools.len() == 1 {
+            Some(pools[0])
+        } else {
+            None
+        };
+        Ok(Iostat {
+            lines: self.spawn_lines("iostat", pool, cmd)?,
+            parser: IostatParser::new(&names, *options),
+        })
+    }
+
+    /// Report events on `pool` (or, with `None`, all pools). With `follow` (`-f`), wait for new
+    /// events after reporting those already posted, until the returned `Events` is dropped.
+    pub async fn events(&self, pool: Option<&str>, follow: bool) -> Result<Events, Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("events").arg("-Hv");
+        if follow {
+            cmd.arg("-f");
+        }
+        cmd.args(pool);
+
+        Ok(Events {
+            lines: self.spawn_lines("events", pool, cmd)?,
+            parser: EventParser::default(),
+        })
+    }
+
+    /// Start `cmd`, to have its output read a line at a time
+    fn spawn_lines(
+        &self,
+        subcommand: &'static str,
+        pool: Option<&str>,
+        mut cmd: Command,
+    ) -> Result<OutputLines, Error> {
+        let span = info_span!(
+            "zpool",
+            subcommand,
+            pool,
+            duration_ms = field::Empty,
+            status = field::Empty,
+        );
+        info!(parent: &span, "run: {:?}", cmd);
+
+        let mut child = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err_with(|| format!("Failed to execute zpool {}", subcommand))?;
+        let stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+
+        // read as it arrives, so a command writing a lot of it doesn't block on a full pipe
+        let stderr = tokio::spawn(async move {
+            let mut msg = Vec::new();
+            let _ = stderr.read_to_end(&mut msg).await;
+            msg
+        });
+
+        Ok(OutputLines {
+            subcommand,
+            child,
+            lines: BufReader::new(stdout).lines(),
+            stderr: Some(stderr),
+            span,
+            start: Instant::now(),
+        })
     }
 
-    pub async fn clear(&self, _pool: &str, _device: Option<&str>) -> Result<(), ()> {
-        unimplemented!();
+    /// Take `device` offline. If `temporary` (`-t`), it returns online on reboot. If `fault`
+    /// (`-f`), it is marked faulted instead.
+    pub async fn offline(
+        &self,
+        pool: &str,
+        device: &str,
+        temporary: bool,
+        fault: bool,
+    ) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("offline");
+        if temporary {
+            cmd.arg("-t");
+        }
+        if fault {
+            cmd.arg("-f");
+        }
+        cmd.arg(pool).arg(device);
+
+        self.run_output("offline", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Bring `device` online. If `expand` (`-e`), grow it to use all available space.
+    pub async fn online(&self, pool: &str, device: &str, expand: bool) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("online");
+        if expand {
+            cmd.arg("-e");
+        }
+        cmd.arg(pool).arg(device);
+
+        self.run_output("online", Some(pool), cmd).await?;
+        Ok(())
     }
 
-    pub async fn list(&self) -> Result<(), ()> {
-        tokio::process::Command::new(&self.zpool_cmd)
-            .arg("list")
-            .arg("-jv")
-            .status()
+    /// Replace `device` (a name, path, or guid) with `new_device`, or with `None`, with a new
+    /// device in the same location. Returns once the replacement starts; the resilver continues
+    /// in the background.
+    ///
+    /// `pool_properties` may only include `ashift`.
+    pub async fn replace(
+        &self,
+        force: bool,
+        pool_properties: &[PoolProperty],
+        pool: &str,
+        device: &str,
+        new_device: Option<&str>,
+    ) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("replace");
+        if force {
+            cmd.arg("-f");
+        }
+        for p in pool_properties.iter() {
+            cmd.arg("-o").arg(format!("{}={}", p.property, p.value));
+        }
+        cmd.arg(pool).arg(device).args(new_device);
+
+        self.run_output("replace", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Detach `device` from its mirror (or from an in progress replacement)
+    pub async fn detach(&self, pool: &str, device: &str) -> Result<(), Error> {
+        let mut cmd = self.cmd();
+        cmd.arg("detach").arg(pool).arg(device);
+
+        self.run_output("detach", Some(pool), cmd).await?;
+        Ok(())
+    }
+
+    /// Replace the leaf vdev `old` (its name, path, or guid) in `pool` with `new_device` and
+    /// wait for the replacement to complete.
+    ///
+    /// `new_device` must be at least as large as `old`. This is checked up front when `old` can
+    /// still be opened; when it can't (a failed disk is often gone) `zpool replace` is left to
+    /// reject a device which is too small. While resilvering, `progress` is called
+    /// every `interval` with the bytes remaining.
+    pub async fn replace_disk<F>(
+        &self,
+        pool: &str,
+        old: &str,
+        new_device: &str,
+        interval: Duration,
+        mut progress: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(u64),
+    {
+        let list = self.list_parsable(Some(pool)).await?;
+        let (guid, old_path) = {
+            let vdev = find_leaf(&list, pool, old)
+                .ok_or_else(|| eyre!("no device {} in pool {}", old, pool))?;
+            (vdev.guid.clone(), vdev.path.clone())
+        };
+
+        // the `size` zpool reports for a leaf excludes its labels (and, on some versions, the
+        // space left over after its metaslabs), so compare the devices themselves instead
+        let new_path = device_path(new_device);
+        let new_size = device_size(&new_path)
+            .await
+            .wrap_err_with(|| format!("Failed to determine size of {}", new_path))?;
+        let old_size = match old_path {
+            Some(p) => device_size(Path::new(&p)).await.ok(),
+            None => None,
+        };
+        if let Some(old_size) = old_size {
+            if new_size < old_size {
+                return Err(eyre!(
+                    "{} ({} bytes) is smaller than {} ({} bytes)",
+                    new_device,
+                    new_size,
+                    old,
+                    old_size
+                )
+                .into());
+            }
+        }
+
+        info!(
+            "replacing {} (guid {}) in {} with {}",
+            old, guid, pool, new_device
+        );
+        self.replace(false, &[], pool, &guid, Some(new_device))
+            .await?;
+        self.wait(
+            pool,
+            &[PoolActivity::Replace],
+            None,
+            Some(interval),
+            |remaining| {
+                progress(remaining.iter().map(|(_, bytes)| bytes).sum());
+            },
+        )
+        .await?;
+
+        // a replacement that fails leaves the old device in place
+        let list = self.list_parsable(Some(pool)).await?;
+        if find_leaf(&list, pool, &guid).is_some() {
+            return Err(eyre!("{} was not replaced by {}", old, new_device).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Output of a running `zpool` command, returned a line at a time. The command is killed when
+/// this is dropped.
+#[derive(Debug)]
+struct OutputLines {
+    subcommand: &'static str,
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+    stderr: Option<JoinHandle<Vec<u8>>>,
+    span: Span,
+    start: Instant,
+}
+
+impl OutputLines {
+    /// The next line of output, or `None` once the command exits successfully
+    async fn next_line(&mut self) -> Result<Option<String>, Error> {
+        let line = self
+            .lines
+            .next_line()
+            .instrument(self.span.clone())
             .await
-            .map_err(|_| ())
-            .and_then(|status| if status.success() { Ok(()) } else { Err(()) })
+            .wrap_err_with(|| format!("Failed to read zpool {} output", self.subcommand))?;
+        if line.is_some() {
+            return Ok(line);
+        }
+
+        let status = self
+            .child
+            .wait()
+            .await
+            .wrap_err_with(|| format!("Failed to wait for zpool {}", self.subcommand))?;
+
+        self.span
+            .record("duration_ms", self.start.elapsed().as_millis() as u64);
+        self.span.record("status", status.code());
+
+        if status.success() {
+            return Ok(None);
+        }
+
+        let msg = match self.stderr.take() {
+            Some(stderr) => stderr.await.unwrap_or_default(),
+            None => Vec::new(),
+        };
+        Err(eyre!(
+            "zpool {} failed: {}",
+            self.subcommand,
+            String::from_utf8_lossy(&msg)
+        )
+        .into())
     }
 }
 
+/// Samples from a running `zpool iostat`, returned by `ZpoolCmd::iostat()`. `zpool iostat` is
+/// killed when this is dropped.
+#[derive(Debug)]
+pub struct Iostat {
+    lines: OutputLines,
+    parser: IostatParser,
+}
+
+impl Iostat {
+    /// The next sample, or `None` once `zpool iostat` exits
+    pub async fn next(&mut self) -> Result<Option<IostatRow>, Error> {
+        while let Some(line) = self.lines.next_line().await? {
+            if let Some(row) = self
+                .parser
+                .parse_line(&line)
+                .wrap_err("Failed to parse zpool iostat output")?
+            {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Events from a running `zpool events`, returned by `ZpoolCmd::events()`. `zpool events` is
+/// killed when this is dropped.
+#[derive(Debug)]
+pub struct Events {
+    lines: OutputLines,
+    parser: EventParser,
+}
+
+impl Events {
+    /// The next event, or `None` once `zpool events` exits (which, when following, it doesn't)
+    pub async fn next(&mut self) -> Result<Option<ZpoolEvent>, Error> {
+        while let Some(line) = self.lines.next_line().await? {
+            if let Some(event) = self
+                .parser
+                .parse_line(&line)
+                .wrap_err("Failed to parse zpool events output")?
+            {
+                return Ok(Some(event));
+            }
+        }
+        Ok(self
+            .parser
+            .finish()
+            .wrap_err("Failed to parse zpool events output")?)
+    }
+}
+
+/// Find the leaf vdev of `pool` in `list` named `device` (by name, path, or guid). Leaves being
+/// replaced are found within their `replacing` vdev.
+pub fn find_leaf<'a>(list: &'a ZpoolList, pool: &str, device: &str) -> Option<&'a ZpoolListVdev> {
+    list.pools
+        .get(pool)?
+        .vdevs
+        .values()
+        .flat_map(|v| v.walk())
+        .filter(|v| v.is_leaf())
+        .find(|v| v.name == device || v.guid == device || v.path.as_deref() == Some(device))
+}
+
+/// The path `zpool` uses for `device`. Names without a `/` (like `sdb`) are looked for in the
+/// same directories `zpool` searches, falling back to `/dev`.
+pub fn device_path(device: &str) -> PathBuf {
+    if device.contains('/') {
+        return PathBuf::from(device);
+    }
+    [
+        "/dev",
+        "/dev/disk/by-id",
+        "/dev/disk/by-vdev",
+        "/dev/mapper",
+    ]
+    .iter()
+    .map(|dir| Path::new(dir).join(device))
+    .find(|p| p.exists())
+    .unwrap_or_else(|| Path::new("/dev").join(device))
+}
+
+/// Size of the block device (or file) at `path`, in bytes
+async fn device_size(path: &Path) -> std::io::Result<u64> {
+    use tokio::io::AsyncSeekExt;
+    tokio::fs::File::open(path)
+        .await?
+        .seek(std::io::SeekFrom::End(0))
+        .await
+}
+
 impl Default for ZpoolCmd {
     fn default() -> Self {
         ZpoolCmd {
@@ -183,7 +1209,73 @@ impl Default for ZpoolCmd {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct PoolProperty {
-    _property: String,
-    _value: String,
+    pub property: String,
+    pub value: String,
+}
+
+impl PoolProperty {
+    pub fn new(property: &str, value: &str) -> Self {
+        PoolProperty {
+            property: property.to_owned(),
+            value: value.to_owned(),
+        }
+    }
+}
+
+/// Options for `ZpoolCmd::import()`
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ImportOptions {
+    /// `-d`: directories (or devices) to search instead of the defaults
+    pub dirs: Vec<String>,
+    /// `-R`: mount filesystems under this directory, and don't cache the pool's configuration
+    pub altroot: Option<String>,
+    /// `-N`: don't mount filesystems
+    pub no_mount: bool,
+    /// `-o readonly=on`
+    pub read_only: bool,
+    /// `-f`: import even if the pool appears to be in use by another system
+    pub force: bool,
+    /// `-D`: import a destroyed pool
+    pub destroyed: bool,
+    /// `--rewind-to-checkpoint`: discard every change made since the pool was checkpointed
+    pub rewind_to_checkpoint: bool,
+    /// `-o`: pool properties to set for this import
+    pub properties: Vec<PoolProperty>,
+}
+
+impl ImportOptions {
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for dir in self.dirs.iter() {
+            args.push("-d".to_owned());
+            args.push(dir.clone());
+        }
+        if let Some(ref altroot) = self.altroot {
+            args.push("-R".to_owned());
+            args.push(altroot.clone());
+        }
+        if self.no_mount {
+            args.push("-N".to_owned());
+        }
+        if self.read_only {
+            args.push("-o".to_owned());
+            args.push("readonly=on".to_owned());
+        }
+        if self.force {
+            args.push("-f".to_owned());
+        }
+        if self.destroyed {
+            args.push("-D".to_owned());
+        }
+        if self.rewind_to_checkpoint {
+            args.push("--rewind-to-checkpoint".to_owned());
+        }
+        for p in self.properties.iter() {
+            args.push("-o".to_owned());
+            args.push(format!("{}={}", p.property, p.value));
+        }
+        args
+    }
 }
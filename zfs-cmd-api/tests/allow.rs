@@ -0,0 +1,75 @@
+extern crate zfs_cmd_api as zfs;
+
+use zfs::allow::{Permissions, Scope, Who};
+
+const ALLOW: &str = "\
+---- Permissions on tank/backup -------------------------------------
+Permission sets:
+\t@recv create,mount,receive
+Create time permissions:
+\tdestroy
+Local+Descendent permissions:
+\tuser backup @recv,destroy
+\tgroup staff hold
+---- Permissions on tank --------------------------------------------
+Local permissions:
+\tuser backup snapshot
+Descendent permissions:
+\teveryone send
+";
+
+#[test]
+fn allow_parse() {
+    let p = Permissions::parse(ALLOW).expect("parse failed");
+    assert_eq!(p.datasets.len(), 2);
+
+    let backup = &p.datasets[0];
+    assert_eq!(backup.dataset, "tank/backup");
+    assert_eq!(backup.sets["@recv"].len(), 3);
+    assert!(backup.create_time.contains("destroy"));
+    assert_eq!(backup.grants.len(), 2);
+    assert_eq!(backup.grants[0].who, Who::User("backup".to_owned()));
+    assert_eq!(backup.grants[0].scope, Scope::LocalAndDescendent);
+    assert_eq!(backup.grants[1].who, Who::Group("staff".to_owned()));
+
+    let tank = &p.datasets[1];
+    assert_eq!(tank.dataset, "tank");
+    assert_eq!(tank.grants[0].scope, Scope::Local);
+    assert_eq!(tank.grants[1].who, Who::Everyone);
+}
+
+#[test]
+fn allow_effective() {
+    let p = Permissions::parse(ALLOW).expect("parse failed");
+
+    let e = p.effective("tank/backup", "backup", &[]);
+    let e: Vec<&str> = e.iter().map(|s| s.as_str()).collect();
+    // `snapshot` is local to `tank`, so does not apply
+    assert_eq!(e, ["create", "destroy", "mount", "receive", "send"]);
+
+    let e = p.effective("tank/backup", "other", &["staff"]);
+    assert!(e.contains("hold"));
+    assert!(!e.contains("receive"));
+}
+
+#[test]
+fn allow_effective_nested_sets() {
+    let p = Permissions::parse(
+        "\
+---- Permissions on tank/backup -------------------------------------
+Permission sets:
+\t@zcopy @recv,destroy
+\t@loop @zcopy,@loop
+Local+Descendent permissions:
+\tgroup backup @loop
+---- Permissions on tank --------------------------------------------
+Permission sets:
+\t@recv create,mount,receive
+",
+    )
+    .expect("parse failed");
+
+    let e = p.effective("tank/backup", "other", &["backup"]);
+    let e: Vec<&str> = e.iter().map(|s| s.as_str()).collect();
+    assert_eq!(e, ["create", "destroy", "mount", "receive"]);
+}
@@ -0,0 +1,114 @@
+extern crate zfs_cmd_api as zfs;
+
+use serde_json::Value;
+use std::path::PathBuf;
+use zfs::program::ProgramOptions;
+use zfs::{OpClass, RecvFlags, Zfs};
+
+/// A `Zfs` which runs `prefix zfs ...` for state changing operations, so `true` and `false`
+/// stand in for a `zfs` which succeeds or fails, logging to a fresh audit log
+fn zfs(prefix: &str, name: &str) -> (Zfs, PathBuf) {
+    let log = std::env::temp_dir().join(format!("zfs-audit-{}-{}.log", std::process::id(), name));
+    let _ = std::fs::remove_file(&log);
+
+    let mut zfs = Zfs::default();
+    zfs.prefix(OpClass::Mutate, vec![prefix.to_owned()])
+        .prefix(OpClass::Read, vec!["false".to_owned()])
+        .audit_log(&log);
+    (zfs, log)
+}
+
+fn records(log: &PathBuf) -> Vec<Value> {
+    let text = std::fs::read_to_string(log).unwrap();
+    std::fs::remove_file(log).unwrap();
+    text.lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect()
+}
+
+#[test]
+fn success() {
+    let (zfs, log) = zfs("true", "success");
+    zfs.set("tank/home", &[("compression", "zstd")]).unwrap();
+
+    let records = records(&log);
+    assert_eq!(records.len(), 1);
+    let r = &records[0];
+    assert!(r["time"].as_u64().unwrap() > 0);
+    assert_eq!(r["pid"].as_u64(), Some(std::process::id() as u64));
+    assert!(r.get("user").is_some());
+    assert_eq!(
+        r["argv"],
+        serde_json::json!(["true", "zfs", "set", "compression=zstd", "tank/home"])
+    );
+    assert_eq!(r["outcome"], serde_json::json!({ "result": "success" }));
+    assert_eq!(r["snapshots_before"], Value::Null);
+}
+
+#[test]
+fn failed() {
+    let (zfs, log) = zfs("false", "failed");
+    assert!(zfs.set("tank/home", &[("compression", "zstd")]).is_err());
+
+    let records = records(&log);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["outcome"]["result"], "failed");
+    assert!(records[0]["outcome"]["error"].as_str().is_some());
+}
+
+#[test]
+fn unknown_on_drop() {
+    let (zfs, log) = zfs("true", "unknown");
+    let recv = zfs
+        .recv("tank/home@a", &[], None, &[], RecvFlags::Force.into())
+        .unwrap();
+    // abandoned without being passed to `send_recv()`
+    drop(recv);
+
+    let records = records(&log);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["argv"][2], "recv");
+    assert_eq!(
+        records[0]["outcome"],
+        serde_json::json!({ "result": "unknown" })
+    );
+}
+
+#[test]
+fn program_without_snapshots() {
+    let (mut zfs, log) = zfs("true", "program");
+    // listing would succeed, so a list of snapshots would be recorded if one were taken
+    zfs.prefix(OpClass::Read, vec!["true".to_owned()]);
+    let _ = zfs.program(&ProgramOptions::default(), "tank", "return 1", &[]);
+
+    let records = records(&log);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["argv"][2], "program");
+    assert_eq!(records[0]["snapshots_before"], Value::Null);
+}
+
+#[test]
+fn prefix_on_remote_host() {
+    std::env::set_var("AUDIT_TEST_ZFS_CMD", "true host zfs");
+    std::env::set_var("AUDIT_TEST_ZFS_MUTATE_PREFIX", "sudo -n");
+    let log = std::env::temp_dir().join(format!("zfs-audit-{}-prefix.log", std::process::id()));
+    let _ = std::fs::remove_file(&log);
+    let mut zfs = Zfs::from_env_prefix("AUDIT_TEST");
+    zfs.audit_log(&log);
+
+    zfs.set("tank/home", &[("atime", "off")]).unwrap();
+
+    assert_eq!(
+        records(&log)[0]["argv"],
+        serde_json::json!([
+            "true",
+            "host",
+            "sudo",
+            "-n",
+            "zfs",
+            "set",
+            "atime=off",
+            "tank/home"
+        ])
+    );
+}
@@ -0,0 +1,34 @@
+extern crate zfs_cmd_api as zfs;
+
+use zfs::destroy::{chunk_snapshots, parse_failures, snapshot_specs, sort_by_order};
+
+#[test]
+fn specs_use_ranges_for_adjacent_runs() {
+    let order = ["a", "b", "c", "d", "e", "f", "g"];
+    assert_eq!(
+        snapshot_specs(&["a", "b", "c", "e", "f", "x"], &order),
+        ["a%c", "e", "f", "x"]
+    );
+    assert_eq!(snapshot_specs(&["a", "b", "c"], &[]), ["a", "b", "c"]);
+}
+
+#[test]
+fn sort_and_chunk() {
+    let sorted = sort_by_order(&["x", "c", "a", "c"], &["a", "b", "c"]);
+    assert_eq!(sorted, ["a", "c", "x"]);
+
+    // "fs@aa,bb" is 8 bytes
+    let chunks = chunk_snapshots("fs", &["aa", "bb", "cc"], 8);
+    assert_eq!(chunks, [vec!["aa", "bb"], vec!["cc"]]);
+}
+
+#[test]
+fn failures() {
+    let f = parse_failures(
+        "cannot destroy snapshot tank/home@a: dataset is busy\n\
+         cannot destroy snapshot tank/home@c: snapshot is cloned\n",
+    );
+    assert_eq!(f.len(), 2);
+    assert_eq!(f[0].snapshot, "tank/home@a");
+    assert_eq!(f[1].reason, "snapshot is cloned");
+}
@@ -0,0 +1,46 @@
+extern crate zfs_cmd_api as zfs;
+
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+use zfs::diff::{ChangeType, DiffRecord, FileType};
+
+#[test]
+fn diff_parse() {
+    let r = DiffRecord::parse_line(b"1697000000.000000500\tM\t/\t/tank/home").unwrap();
+    assert_eq!(r.time, UNIX_EPOCH + Duration::new(1697000000, 500));
+    assert_eq!(r.change, ChangeType::Modified);
+    assert_eq!(r.file_type, FileType::Directory);
+    assert_eq!(r.path, Path::new("/tank/home"));
+    assert_eq!(r.new_path, None);
+
+    let r = DiffRecord::parse_line(b"1697000000.0\tR\tF\t/tank/home/a\\0040b\t/tank/home/c\\0134d")
+        .unwrap();
+    assert_eq!(r.change, ChangeType::Renamed);
+    assert_eq!(r.path, Path::new("/tank/home/a b"));
+    assert_eq!(r.new_path.as_deref(), Some(Path::new("/tank/home/c\\d")));
+
+    assert!(DiffRecord::parse_line(b"1697000000.0\tR\tF\t/tank/home/a").is_err());
+    assert!(DiffRecord::parse_line(b"1697000000.0\t+\tF\t/tank/home/a\\09").is_err());
+}
+
+#[test]
+fn diff_stderr_flood() {
+    // stands in for a `zfs diff` which writes more to stderr than a pipe holds before its output
+    let script =
+        "head -c 1000000 /dev/zero >&2; printf '1697000000.0\\tM\\t/\\t/tank/home\\n'; exit 1";
+    let mut zfs = zfs::Zfs::default();
+    zfs.prefix(
+        zfs::OpClass::Read,
+        vec!["sh".to_owned(), "-c".to_owned(), script.to_owned()],
+    );
+
+    let mut diff = zfs.diff("tank/home@a", "tank/home@b").unwrap();
+    assert_eq!(diff.next().unwrap().unwrap().change, ChangeType::Modified);
+    match diff.next() {
+        Some(Err(zfs::ZfsError::Process { cmd_info })) => {
+            assert_eq!(cmd_info.stderr().len(), 1000000)
+        }
+        r => panic!("expected failure, got {:?}", r.map(|r| r.is_ok())),
+    }
+    assert!(diff.next().is_none());
+}
@@ -0,0 +1,242 @@
+extern crate zfs_cmd_api as zfs;
+
+use enumflags2::BitFlags;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use zfs::encryption::{EncryptionInfo, KeySource, KeyStatus, LoadKeyFlags};
+use zfs::{OpClass, Zfs, ZfsError};
+
+fn row(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| (*s).to_owned()).collect()
+}
+
+/// A `Zfs` whose state changing operations run a script which records its arguments (one per
+/// line) and stdin in the returned directory, then writes `stderr` and exits with `status`
+fn fake_zfs(name: &str, stderr: &str, status: i32) -> (Zfs, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("zfs-key-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("zfs");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\n\
+             printf '%s\\n' \"$@\" > {dir}/args\n\
+             cat > {dir}/stdin\n\
+             printf '{stderr}' >&2\n\
+             exit {status}\n",
+            dir = dir.display(),
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut zfs = Zfs::default();
+    zfs.prefix(OpClass::Mutate, vec![script.display().to_string()]);
+    (zfs, dir)
+}
+
+fn args(dir: &Path) -> Vec<String> {
+    std::fs::read_to_string(dir.join("args"))
+        .unwrap()
+        .lines()
+        .map(|l| l.to_owned())
+        .collect()
+}
+
+fn stdin(dir: &Path) -> Vec<u8> {
+    std::fs::read(dir.join("stdin")).unwrap()
+}
+
+#[test]
+fn info() {
+    let e = EncryptionInfo::from_list_row(&row(&[
+        "tank/enc",
+        "aes-256-gcm",
+        "tank/enc",
+        "available",
+        "prompt",
+        "passphrase",
+    ]))
+    .unwrap();
+    assert_eq!(e.encryption.as_deref(), Some("aes-256-gcm"));
+    assert_eq!(e.key_status, Some(KeyStatus::Available));
+    assert_eq!(e.key_location.as_deref(), Some("prompt"));
+    assert!(e.is_encryption_root());
+
+    // inherits its key, so has no `keylocation` of its own
+    let e = EncryptionInfo::from_list_row(&row(&[
+        "tank/enc/home",
+        "aes-256-gcm",
+        "tank/enc",
+        "unavailable",
+        "none",
+        "passphrase",
+    ]))
+    .unwrap();
+    assert_eq!(e.key_status, Some(KeyStatus::Unavailable));
+    assert_eq!(e.key_location, None);
+    assert!(!e.is_encryption_root());
+
+    let e = EncryptionInfo::from_list_row(&row(&["tank/home", "off", "-", "-", "none", "none"]))
+        .unwrap();
+    assert_eq!(e.encryption, None);
+    assert_eq!(e.encryption_root, None);
+    assert_eq!(e.key_status, None);
+    assert_eq!(e.key_format, None);
+
+    assert!(EncryptionInfo::from_list_row(&row(&["tank/home", "off"])).is_err());
+    assert!(EncryptionInfo::from_list_row(&row(&[
+        "tank/enc",
+        "aes-256-gcm",
+        "tank/enc",
+        "maybe",
+        "prompt",
+        "raw"
+    ]))
+    .is_err());
+}
+
+#[test]
+fn load_key_args() {
+    let (zfs, dir) = fake_zfs("load", "", 0);
+
+    zfs.load_key(BitFlags::default(), KeySource::Property, "tank/enc")
+        .unwrap();
+    assert_eq!(args(&dir), ["zfs", "load-key", "tank/enc"]);
+    assert!(stdin(&dir).is_empty());
+
+    zfs.load_key(
+        LoadKeyFlags::Recursive | LoadKeyFlags::DryRun,
+        KeySource::Location("file:///etc/keys/enc"),
+        "tank/enc",
+    )
+    .unwrap();
+    assert_eq!(
+        args(&dir),
+        [
+            "zfs",
+            "load-key",
+            "-rn",
+            "-L",
+            "file:///etc/keys/enc",
+            "tank/enc"
+        ]
+    );
+
+    zfs.load_key(
+        BitFlags::default(),
+        KeySource::Data(b"secret\n"),
+        "tank/enc",
+    )
+    .unwrap();
+    assert_eq!(args(&dir), ["zfs", "load-key", "-L", "prompt", "tank/enc"]);
+    assert_eq!(stdin(&dir), b"secret\n");
+}
+
+#[test]
+fn change_key_args() {
+    let (zfs, dir) = fake_zfs("change", "", 0);
+
+    zfs.change_key(false, &[], KeySource::Property, "tank/enc")
+        .unwrap();
+    assert_eq!(args(&dir), ["zfs", "change-key", "tank/enc"]);
+    assert!(stdin(&dir).is_empty());
+
+    zfs.change_key(
+        true,
+        &[("keyformat", "raw")],
+        KeySource::Location("file:///etc/keys/enc"),
+        "tank/enc",
+    )
+    .unwrap();
+    assert_eq!(
+        args(&dir),
+        [
+            "zfs",
+            "change-key",
+            "-l",
+            "-o",
+            "keylocation=file:///etc/keys/enc",
+            "-o",
+            "keyformat=raw",
+            "tank/enc"
+        ]
+    );
+
+    zfs.change_key(
+        false,
+        &[("keyformat", "passphrase")],
+        KeySource::Data(b"new secret\n"),
+        "tank/enc",
+    )
+    .unwrap();
+    assert_eq!(
+        args(&dir),
+        [
+            "zfs",
+            "change-key",
+            "-o",
+            "keylocation=prompt",
+            "-o",
+            "keyformat=passphrase",
+            "tank/enc"
+        ]
+    );
+    assert_eq!(stdin(&dir), b"new secret\n");
+}
+
+#[test]
+fn incorrect_key() {
+    let (zfs, _) = fake_zfs(
+        "incorrect",
+        "Key load error: Incorrect key provided for \\047tank/enc\\047.\\n",
+        255,
+    );
+    match zfs.load_key(BitFlags::default(), KeySource::Property, "tank/enc") {
+        Err(ZfsError::IncorrectKey { dataset, .. }) => assert_eq!(dataset, "tank/enc"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn key_already_loaded_recursive() {
+    // `-r` reports each encryption root, then a summary
+    let (zfs, _) = fake_zfs(
+        "loaded",
+        "Key load error: Key already loaded for \\047tank/enc/a\\047.\\n\
+         Key load error: Incorrect key provided for \\047tank/enc/b\\047.\\n\
+         0 / 2 key(s) successfully loaded\\n",
+        255,
+    );
+    match zfs.load_key(
+        BitFlags::from(LoadKeyFlags::Recursive),
+        KeySource::Property,
+        "tank/enc",
+    ) {
+        Err(ZfsError::KeyAlreadyLoaded { dataset, .. }) => assert_eq!(dataset, "tank/enc/a"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn key_already_unloaded() {
+    let (zfs, _) = fake_zfs(
+        "unloaded",
+        "Key unload error: Key already unloaded for \\047tank/enc\\047.\\n\
+         0 / 1 key(s) successfully unloaded\\n",
+        255,
+    );
+    match zfs.unload_key(true, "tank/enc") {
+        Err(ZfsError::KeyAlreadyUnloaded { dataset, .. }) => assert_eq!(dataset, "tank/enc"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn other_key_error() {
+    let (zfs, _) = fake_zfs("other", "cannot open \\047tank/x\\047: no such pool\\n", 1);
+    match zfs.unload_key(false, "tank/x") {
+        Err(ZfsError::CannotOpen { .. }) => {}
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
@@ -0,0 +1,141 @@
+extern crate zfs_cmd_api as zfs;
+
+use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, UNIX_EPOCH};
+use zfs::events::{parse_event, EventKind, EventParser, EventValue, ZpoolEvent};
+use zfs::zpool::ZpoolCmd;
+
+fn events() -> Vec<ZpoolEvent> {
+    let mut parser = EventParser::default();
+    let mut events: Vec<ZpoolEvent> = include_str!("events.txt")
+        .lines()
+        .filter_map(|l| parser.parse_line(l).unwrap())
+        .collect();
+    events.extend(parser.finish().unwrap());
+    events
+}
+
+#[test]
+fn fixture() {
+    let events = events();
+    let kinds: Vec<EventKind> = events.iter().map(|e| e.kind()).collect();
+    assert_eq!(
+        kinds,
+        [
+            EventKind::PoolImport,
+            EventKind::ChecksumError,
+            EventKind::ScrubFinish,
+            EventKind::DeviceRemoved
+        ]
+    );
+    assert!(events.iter().all(|e| e.pool.as_deref() == Some("tank")));
+
+    let checksum = &events[1];
+    assert_eq!(checksum.eid, Some(0x29));
+    assert_eq!(checksum.vdev_guid, Some(0x9c1e6f3b2a4d5e01));
+    assert_eq!(
+        checksum.vdev_path.as_deref(),
+        Some("/dev/disk/by-id/ata-WDC_WD40EFRX-68N32N0_WD-WCC7K1234567-part1")
+    );
+    assert_eq!(
+        checksum.time,
+        Some(UNIX_EPOCH + Duration::new(0x68f304b1, 0x35cb9457))
+    );
+    assert_eq!(
+        checksum.payload["bad_ranges"],
+        EventValue::Numbers(vec![0, 0x20000])
+    );
+    match checksum.payload["detector"] {
+        EventValue::Nvlist(ref d) => {
+            assert_eq!(d["scheme"], EventValue::String("zfs".to_owned()));
+            assert_eq!(d["vdev"], EventValue::Number(0x9c1e6f3b2a4d5e01));
+        }
+        ref v => panic!("expected nvlist, got {:?}", v),
+    }
+    assert_eq!(
+        checksum.payload["zio_offset"],
+        EventValue::Number(0x1a2b3c000)
+    );
+
+    assert_eq!(
+        events[3].payload["vdev_state"],
+        EventValue::Other("REMOVED (0x5)".to_owned())
+    );
+}
+
+#[test]
+fn nvlist_array() {
+    let event = parse_event(
+        "Oct 18 2026 03:00:00.000000000\tsysevent.fs.zfs.example
+        children = (2 embedded nvlists)
+        children[0] = (embedded nvlist)
+                path = \"/dev/sda\"
+        (end children[0])
+        children[1] = (embedded nvlist)
+                path = \"/dev/sdb\"
+        (end children[1])
+        (end children)
+        names = \"a\" \"b c\" 
+",
+    )
+    .unwrap();
+
+    assert_eq!(event.class, "sysevent.fs.zfs.example");
+    assert_eq!(event.time, None);
+    match event.payload["children"] {
+        EventValue::Nvlists(ref l) => {
+            assert_eq!(l.len(), 2);
+            assert_eq!(l[1]["path"], EventValue::String("/dev/sdb".to_owned()));
+        }
+        ref v => panic!("expected nvlist array, got {:?}", v),
+    }
+    assert_eq!(
+        event.payload["names"],
+        EventValue::Strings(vec!["a".to_owned(), "b c".to_owned()])
+    );
+
+    assert!(
+        parse_event("Oct 18 2026 03:00:00.000000000\tx\n        a = (embedded nvlist)\n").is_err()
+    );
+}
+
+#[test]
+fn follow() {
+    // a `zpool events -f` which floods stderr, reports the fixture's events, and keeps running
+    let dir = std::env::temp_dir().join(format!("zpool-{}-events", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("zpool");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\nhead -c 1048576 /dev/zero >&2\ncat {}/tests/events.txt\nexec sleep 60\n",
+            env!("CARGO_MANIFEST_DIR")
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let zpool = ZpoolCmd::new(script.to_str().unwrap());
+
+    let kinds = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let mut events = zpool.events(None, true).await.unwrap();
+            let mut kinds = Vec::new();
+            // the last event isn't complete until another follows it
+            for _ in 0..3 {
+                kinds.push(events.next().await.unwrap().unwrap().kind());
+            }
+            kinds
+        });
+
+    assert_eq!(
+        kinds,
+        [
+            EventKind::PoolImport,
+            EventKind::ChecksumError,
+            EventKind::ScrubFinish
+        ]
+    );
+}
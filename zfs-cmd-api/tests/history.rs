@@ -0,0 +1,79 @@
+extern crate zfs_cmd_api as zfs;
+
+use std::time::{Duration, UNIX_EPOCH};
+use zfs::history::{filter, format_time, parse_history, HistoryEvent};
+
+#[test]
+fn parse() {
+    let records = parse_history(include_str!("history.txt")).unwrap();
+    assert_eq!(records.len(), 8);
+
+    let create = &records[0];
+    assert_eq!(create.time, UNIX_EPOCH + Duration::from_secs(1602375841));
+    assert_eq!(format_time(create.time), "2020-10-11.00:24:01");
+    assert_eq!(format_time(records[7].time), "2021-03-01.12:00:00");
+    assert_eq!(
+        create.event,
+        HistoryEvent::Command("zpool create tank mirror /dev/sda /dev/sdb".to_owned())
+    );
+    assert_eq!(create.uid, Some(0));
+    assert_eq!(create.user.as_deref(), Some("root"));
+    assert_eq!(create.host.as_deref(), Some("host1"));
+    assert_eq!(create.zone.as_deref(), Some("linux"));
+
+    let snapshot = &records[2];
+    assert_eq!(snapshot.txg, Some(120));
+    assert_eq!(
+        snapshot.event,
+        HistoryEvent::Internal {
+            event: "snapshot".to_owned(),
+            dataset: Some("tank/home@a".to_owned()),
+            dataset_id: Some(390),
+            detail: String::new(),
+        }
+    );
+    assert_eq!(snapshot.uid, None);
+
+    match records[4].event {
+        HistoryEvent::Internal {
+            ref dataset,
+            ref detail,
+            ..
+        } => {
+            assert_eq!(dataset.as_deref(), Some("tank/my data"));
+            assert_eq!(detail, "compression=15");
+        }
+        ref e => panic!("expected internal event, got {:?}", e),
+    }
+
+    let ioctl = &records[6];
+    match ioctl.event {
+        HistoryEvent::Ioctl {
+            ref name,
+            ref detail,
+        } => {
+            assert_eq!(name, "destroy_snaps");
+            assert!(detail.contains("tank/home@a"));
+        }
+        ref e => panic!("expected ioctl, got {:?}", e),
+    }
+    assert_eq!(ioctl.uid, Some(1001));
+    assert_eq!(ioctl.user, None);
+}
+
+#[test]
+fn filtering() {
+    let records = parse_history(include_str!("history.txt")).unwrap();
+
+    let snap = filter(&records, Some("tank/home@a"), None, None);
+    assert_eq!(snap.len(), 5);
+    assert_eq!(filter(&records, Some("tank/home"), None, None).len(), 5);
+    assert_eq!(filter(&records, Some("tank/hom"), None, None).len(), 0);
+
+    let since = UNIX_EPOCH + Duration::from_secs(1614556800);
+    let recent = filter(&records, Some("tank/home@a"), Some(since), None);
+    assert_eq!(recent.len(), 3);
+    assert!(recent.iter().all(|r| r.host.as_deref() == Some("host2")));
+
+    assert!(parse_history("not history\n").is_err());
+}
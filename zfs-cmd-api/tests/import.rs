@@ -0,0 +1,66 @@
+extern crate zfs_cmd_api as zfs;
+
+use zfs::status::{parse_import, VdevState};
+use zfs::zpool::{ImportOptions, PoolProperty};
+
+#[test]
+fn discover() {
+    let pools = parse_import(include_str!("import.txt")).unwrap();
+    assert_eq!(pools.len(), 2);
+
+    let backup = &pools[0];
+    assert_eq!(backup.id, 6904587473384093231);
+    assert!(!backup.destroyed);
+    assert_eq!(backup.pool.name, "backup");
+    assert_eq!(backup.pool.state, VdevState::Online);
+    let mirror = &backup.pool.root.as_ref().unwrap().children[0];
+    assert_eq!(mirror.children[1].name, "/tmp/zoop/zdev2.img");
+
+    let offsite = &pools[1];
+    assert_eq!(offsite.id, 11845232925421776090);
+    assert_eq!(offsite.pool.state, VdevState::Degraded);
+    assert!(offsite
+        .pool
+        .action
+        .as_ref()
+        .unwrap()
+        .contains("fault tolerance"));
+    let mirror = &offsite.pool.root.as_ref().unwrap().children[0];
+    assert_eq!(mirror.children[1].state, VdevState::Unavail);
+    assert_eq!(offsite.pool.logs[0].name, "/tmp/zoop/zlog.img");
+}
+
+#[test]
+fn destroyed() {
+    let pools = parse_import(
+        "   pool: old\n     id: 1\n  state: ONLINE (DESTROYED)\n config:\n\n\told  ONLINE\n",
+    )
+    .unwrap();
+    assert!(pools[0].destroyed);
+    assert_eq!(pools[0].pool.state, VdevState::Online);
+}
+
+#[test]
+fn options() {
+    let options = ImportOptions {
+        altroot: Some("/mnt/backup".to_owned()),
+        no_mount: true,
+        read_only: true,
+        rewind_to_checkpoint: true,
+        properties: vec![PoolProperty::new("cachefile", "none")],
+        ..Default::default()
+    };
+    assert_eq!(
+        options.args(),
+        [
+            "-R",
+            "/mnt/backup",
+            "-N",
+            "-o",
+            "readonly=on",
+            "--rewind-to-checkpoint",
+            "-o",
+            "cachefile=none"
+        ]
+    );
+}
@@ -0,0 +1,176 @@
+extern crate zfs_cmd_api as zfs;
+
+use std::os::unix::fs::PermissionsExt;
+use std::time::Duration;
+use zfs::iostat::{IostatOptions, IostatParser, IostatRow, IostatSample, QueueDepth};
+use zfs::zpool::ZpoolCmd;
+
+fn rows(parser: &mut IostatParser, output: &str) -> Vec<IostatRow> {
+    output
+        .lines()
+        .filter_map(|l| parser.parse_line(l).unwrap())
+        .collect()
+}
+
+#[test]
+fn default() {
+    let output = "\
+tank\t1099511627776\t3298534883328\t12\t340\t1048576\t41943040
+mirror-0\t1099511627776\t3298534883328\t12\t340\t1048576\t41943040
+sda\t-\t-\t6\t170\t524288\t20971520
+sdb\t-\t-\t6\t170\t524288\t20971520
+
+logs\t-\t-\t-\t-\t-\t-
+nvme0n1\t65536\t17179803648\t0\t20\t0\t81920
+tank\t1099511627776\t3298534883328\t0\t0\t0\t0
+";
+    let mut parser = IostatParser::new(&["tank"], IostatOptions::default());
+    let rows = rows(&mut parser, output);
+
+    assert_eq!(rows.len(), 7);
+    assert_eq!(rows[0].vdev, None);
+    assert_eq!(rows[2].vdev.as_deref(), Some("sda"));
+    assert_eq!(rows[6].interval, 1);
+    assert!(rows[..6]
+        .iter()
+        .all(|r| r.interval == 0 && r.pool == "tank"));
+
+    match rows[2].sample {
+        IostatSample::Stats(ref s) => {
+            assert_eq!(s.alloc, None);
+            assert_eq!(s.write_ops, Some(170));
+            assert_eq!(s.write_bytes, Some(20971520));
+            assert!(s.latency.is_none());
+        }
+        _ => panic!("expected stats"),
+    }
+}
+
+#[test]
+fn latency_and_queues() {
+    let options = IostatOptions {
+        latency: true,
+        queues: true,
+        histograms: false,
+    };
+    let mut parser = IostatParser::new(&["tank"], options);
+    let line = "tank\t10\t20\t1\t2\t3\t4\
+        \t100\t200\t110\t210\t-\t-\t120\t220\t5000\t-\t300\
+        \t0\t0\t1\t2\t0\t0\t3\t4\t0\t0\t0\t0\t-\t-";
+    let row = parser.parse_line(line).unwrap().unwrap();
+
+    let stats = match row.sample {
+        IostatSample::Stats(s) => s,
+        _ => panic!("expected stats"),
+    };
+    let latency = stats.latency.unwrap();
+    assert_eq!(latency.disk_write, Some(210));
+    assert_eq!(latency.sync_queue_read, None);
+    assert_eq!(latency.rebuild, Some(300));
+    let queues = stats.queues.unwrap();
+    assert_eq!(
+        queues.sync_write,
+        QueueDepth {
+            pending: Some(1),
+            active: Some(2)
+        }
+    );
+    assert_eq!(
+        queues.rebuild,
+        Some(QueueDepth {
+            pending: None,
+            active: None
+        })
+    );
+
+    assert!(parser.parse_line("tank\t10\t20\t1\t2\t3\t4\t100").is_err());
+}
+
+#[test]
+fn histograms() {
+    let options = IostatOptions {
+        histograms: true,
+        ..Default::default()
+    };
+    assert!(IostatOptions {
+        latency: true,
+        ..options
+    }
+    .args()
+    .is_err());
+
+    let output = "\
+tank
+512\t0\t0\t0\t0\t10\t2\t0\t0\t0\t0\t0\t0
+4096\t5\t1\t0\t0\t-\t-\t0\t0\t0\t0\t0\t0
+sda
+512\t0\t0\t0\t0\t5\t1\t0\t0\t0\t0\t0\t0
+";
+    let mut parser = IostatParser::new(&["tank"], options);
+    let rows = rows(&mut parser, output);
+
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[2].vdev.as_deref(), Some("sda"));
+    match rows[1].sample {
+        IostatSample::Histogram(ref b) => {
+            assert_eq!(b.size, 4096);
+            assert_eq!(b.counts[0], Some(5));
+            assert_eq!(b.counts[4], None);
+        }
+        _ => panic!("expected histogram"),
+    }
+}
+
+#[test]
+fn running() {
+    // a `zpool` with one pool, whose `iostat` records its arguments and floods stderr before
+    // reporting a sample
+    let dir = std::env::temp_dir().join(format!("zpool-{}-iostat", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("args");
+    let script = dir.join("zpool");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\n\
+             case \"$1\" in\n\
+             list) echo tank ;;\n\
+             iostat) echo \"$@\" > {}; head -c 1048576 /dev/zero >&2; \
+             printf 'tank\\t1\\t2\\t3\\t4\\t5\\t6\\n' ;;\n\
+             esac\n",
+            log.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let zpool = ZpoolCmd::new(script.to_str().unwrap());
+
+    let rows = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let mut iostat = zpool
+                .iostat(
+                    &[],
+                    &IostatOptions::default(),
+                    Duration::from_secs(1),
+                    Some(1),
+                )
+                .await
+                .unwrap();
+            let mut rows = Vec::new();
+            while let Some(row) = iostat.next().await.unwrap() {
+                rows.push(row);
+            }
+            rows
+        });
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].pool, "tank");
+    // the listed pools are passed on, rather than left for `zpool iostat` to list again
+    assert_eq!(
+        std::fs::read_to_string(&log).unwrap().trim_end(),
+        "iostat -Hpv tank 1 1"
+    );
+}
@@ -0,0 +1,131 @@
+extern crate zfs_cmd_api as zfs;
+
+use std::time::{Duration, UNIX_EPOCH};
+use zfs::maintenance::{scrub_due, CheckpointAction, ScrubAction, ScrubDecision, TrimAction};
+use zfs::status::{parse_text, PoolStatus, ScanFunction, ScanState, ScanStatus, VdevState};
+
+const DAY: u64 = 24 * 60 * 60;
+
+fn pool(scan: Option<(ScanFunction, ScanState, Option<u64>)>) -> PoolStatus {
+    PoolStatus {
+        name: "tank".to_owned(),
+        state: VdevState::Online,
+        status: None,
+        action: None,
+        scan: scan.map(|(function, state, end)| ScanStatus {
+            function,
+            state,
+            start_time: None,
+            end_time: end.map(|e| UNIX_EPOCH + Duration::from_secs(e)),
+            to_examine: None,
+            examined: None,
+            issued: None,
+            errors: None,
+            percent_done: None,
+            eta: None,
+        }),
+        root: None,
+        logs: vec![],
+        cache: vec![],
+        spares: vec![],
+        error_count: None,
+        errors: vec![],
+    }
+}
+
+#[test]
+fn args() {
+    assert!(ScrubAction::Start.args(false).is_empty());
+    assert_eq!(ScrubAction::Pause.args(true), ["-e", "-p"]);
+    assert_eq!(
+        TrimAction::Start {
+            secure: true,
+            rate: Some(1 << 20)
+        }
+        .args(),
+        ["-d", "-r", "1048576"]
+    );
+    assert_eq!(TrimAction::Suspend.args(), ["-s"]);
+    assert!(CheckpointAction::Create.args().is_empty());
+    assert_eq!(
+        CheckpointAction::Discard { wait: true }.args(),
+        ["-d", "-w"]
+    );
+}
+
+#[test]
+fn due() {
+    let now = UNIX_EPOCH + Duration::from_secs(100 * DAY);
+    let interval = Duration::from_secs(30 * DAY);
+
+    assert_eq!(scrub_due(&pool(None), now, interval), ScrubDecision::Due);
+    assert_eq!(
+        scrub_due(
+            &pool(Some((
+                ScanFunction::Scrub,
+                ScanState::Finished,
+                Some(90 * DAY)
+            ))),
+            now,
+            interval
+        ),
+        ScrubDecision::NotDue(UNIX_EPOCH + Duration::from_secs(120 * DAY))
+    );
+    assert_eq!(
+        scrub_due(
+            &pool(Some((
+                ScanFunction::Resilver,
+                ScanState::Finished,
+                Some(60 * DAY)
+            ))),
+            now,
+            interval
+        ),
+        ScrubDecision::Due
+    );
+    assert_eq!(
+        scrub_due(
+            &pool(Some((ScanFunction::Scrub, ScanState::Scanning, None))),
+            now,
+            interval
+        ),
+        ScrubDecision::InProgress(ScanFunction::Scrub)
+    );
+    assert_eq!(
+        scrub_due(
+            &pool(Some((
+                ScanFunction::Scrub,
+                ScanState::Canceled,
+                Some(99 * DAY)
+            ))),
+            now,
+            interval
+        ),
+        ScrubDecision::Due
+    );
+    assert_eq!(
+        scrub_due(
+            &pool(Some((ScanFunction::Scrub, ScanState::Finished, None))),
+            now,
+            interval
+        ),
+        ScrubDecision::Due
+    );
+}
+
+#[test]
+fn due_from_text() {
+    // without `-j`, the end time comes from `... on Sun Oct  8 00:24:05 2023`
+    let pools = parse_text(include_str!("status.txt")).unwrap();
+    let end = UNIX_EPOCH + Duration::from_secs(1696724645);
+    let interval = Duration::from_secs(30 * DAY);
+
+    assert_eq!(
+        scrub_due(&pools[1], end + Duration::from_secs(DAY), interval),
+        ScrubDecision::NotDue(end + interval)
+    );
+    assert_eq!(
+        scrub_due(&pools[1], end + interval, interval),
+        ScrubDecision::Due
+    );
+}
@@ -0,0 +1,36 @@
+extern crate zfs_cmd_api as zfs;
+
+use zfs::mount::{parse_unmount_error, CanMount, MountInfo, Mountpoint};
+
+fn row(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| (*s).to_owned()).collect()
+}
+
+#[test]
+fn mount_info() {
+    let m = MountInfo::from_list_row(&row(&["tank/home", "yes", "/home", "on"])).unwrap();
+    assert!(m.mounted);
+    assert_eq!(m.mountpoint, Some(Mountpoint::Path("/home".to_owned())));
+    assert_eq!(m.canmount, Some(CanMount::On));
+
+    let m = MountInfo::from_list_row(&row(&["tank/b", "no", "legacy", "noauto"])).unwrap();
+    assert!(!m.mounted);
+    assert_eq!(m.mountpoint, Some(Mountpoint::Legacy));
+    assert_eq!(m.canmount, Some(CanMount::NoAuto));
+
+    assert!(MountInfo::from_list_row(&row(&["tank/b", "maybe", "none", "on"])).is_err());
+}
+
+#[test]
+fn unmount_error() {
+    let stderr = "umount: /tank/home/var: target is busy.\n\
+                  cannot unmount '/tank/home/var': umount failed\n";
+    assert_eq!(
+        parse_unmount_error(stderr),
+        Some(("/tank/home/var".to_owned(), "umount failed".to_owned()))
+    );
+    assert_eq!(
+        parse_unmount_error("cannot open 'tank': no such pool\n"),
+        None
+    );
+}
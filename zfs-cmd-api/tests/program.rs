@@ -0,0 +1,27 @@
+extern crate zfs_cmd_api as zfs;
+
+use serde_json::json;
+use zfs::program::{parse_error, parse_output};
+
+#[test]
+fn output() {
+    assert_eq!(
+        parse_output(r#"{"return": {"destroyed": ["tank/home@a"]}}"#).unwrap(),
+        json!({"destroyed": ["tank/home@a"]})
+    );
+    assert_eq!(parse_output(r#"{"return": 3}"#).unwrap(), json!(3));
+    assert_eq!(parse_output("{}").unwrap(), json!(null));
+    assert!(parse_output("Channel program fully executed").is_err());
+}
+
+#[test]
+fn error() {
+    assert_eq!(
+        parse_error(
+            "Channel program execution failed:\n[string \"channel program\"]:1: bad argument\n"
+        )
+        .unwrap(),
+        "[string \"channel program\"]:1: bad argument"
+    );
+    assert_eq!(parse_error("cannot open 'tank': no such pool\n"), None);
+}
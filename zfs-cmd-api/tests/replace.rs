@@ -0,0 +1,154 @@
+extern crate zfs_cmd_api as zfs;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::time::Duration;
+use zfs::zpool::{device_path, find_leaf, PoolProperty, ZpoolCmd, ZpoolList};
+
+/// A `zpool` which records its arguments (one invocation per line) in the returned log
+fn fake_zpool(name: &str) -> (ZpoolCmd, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("zpool-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("args");
+    let script = dir.join("zpool");
+    std::fs::write(
+        &script,
+        format!("#!/bin/sh\necho \"$@\" >> {}\n", log.display()),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    (ZpoolCmd::new(script.to_str().unwrap()), log)
+}
+
+fn block_on<F: std::future::Future>(f: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(f)
+}
+
+#[test]
+fn args() {
+    let (zpool, log) = fake_zpool("args");
+    block_on(async {
+        zpool.offline("tank", "sda", true, false).await.unwrap();
+        zpool.offline("tank", "sda", false, true).await.unwrap();
+        zpool.online("tank", "sda", true).await.unwrap();
+        zpool
+            .replace(
+                true,
+                &[PoolProperty::new("ashift", "12")],
+                "tank",
+                "sda",
+                Some("sdb"),
+            )
+            .await
+            .unwrap();
+        zpool
+            .replace(false, &[], "tank", "sda", None)
+            .await
+            .unwrap();
+        zpool.detach("tank", "sdb").await.unwrap();
+    });
+
+    let args = std::fs::read_to_string(&log).unwrap();
+    assert_eq!(
+        args.lines().collect::<Vec<_>>(),
+        [
+            "offline -t tank sda",
+            "offline -f tank sda",
+            "online -e tank sda",
+            "replace -f -o ashift=12 tank sda sdb",
+            "replace tank sda",
+            "detach tank sdb",
+        ]
+    );
+}
+
+#[test]
+fn leaf() {
+    let list: ZpoolList = serde_json::from_str(include_str!("data.json")).unwrap();
+
+    // within `replacing-1`, by name, path, and guid
+    let by_name = find_leaf(&list, "tank", "i01").unwrap();
+    assert_eq!(by_name.guid, "3870533176997832083");
+    let by_path = find_leaf(&list, "tank", "/dev/disk/by-id/dm-name-z8.2").unwrap();
+    assert_eq!(by_path.name, "dm-name-z8.2");
+    let by_guid = find_leaf(&list, "tank", "3870533176997832083").unwrap();
+    assert_eq!(by_guid.name, "i01");
+
+    // interior vdevs aren't leaves
+    assert!(find_leaf(&list, "tank", "replacing-1").is_none());
+    assert!(find_leaf(&list, "tank", "mirror-3").is_none());
+    // nor are other pools' leaves
+    assert!(find_leaf(&list, "mainrust", "i01").is_none());
+    assert!(find_leaf(&list, "nopool", "i01").is_none());
+}
+
+#[test]
+fn short_names() {
+    assert_eq!(device_path("null"), "/dev/null");
+    assert_eq!(device_path("no-such-disk"), "/dev/no-such-disk");
+    assert_eq!(device_path("./disk.img"), "./disk.img");
+    assert_eq!(device_path("/dev/disk/by-id/x"), "/dev/disk/by-id/x");
+}
+
+#[test]
+fn replace_smaller() {
+    let dir = std::env::temp_dir().join(format!("zpool-{}-smaller", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let old = dir.join("old.img");
+    let new = dir.join("new.img");
+    std::fs::File::create(&old)
+        .unwrap()
+        .set_len(2 << 20)
+        .unwrap();
+    std::fs::File::create(&new)
+        .unwrap()
+        .set_len(1 << 20)
+        .unwrap();
+
+    // `size` is what zpool reports for the leaf, smaller than the file itself
+    std::fs::write(
+        dir.join("list.json"),
+        format!(
+            r#"{{
+  "output_version": {{"command": "zpool list", "vers_major": 0, "vers_minor": 1}},
+  "pools": {{"tank": {{
+    "name": "tank", "type": "POOL", "state": "ONLINE", "pool_guid": "1", "txg": "1",
+    "spa_version": "5000", "zpl_version": "5", "properties": {{}},
+    "vdevs": {{"{old}": {{
+      "name": "{old}", "vdev_type": "file", "guid": "2", "path": "{old}",
+      "class": "normal", "state": "ONLINE",
+      "properties": {{"size": {{"value": "1000", "source": {{"type": "NONE", "data": "-"}}}}}}
+    }}}}
+  }}}}
+}}"#,
+            old = old.display()
+        ),
+    )
+    .unwrap();
+    let script = dir.join("zpool");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\n[ \"$1\" = list ] && exec cat {dir}/list.json\necho \"$@\" >> {dir}/args\n",
+            dir = dir.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let zpool = ZpoolCmd::new(script.to_str().unwrap());
+
+    let e = block_on(zpool.replace_disk(
+        "tank",
+        old.to_str().unwrap(),
+        new.to_str().unwrap(),
+        Duration::from_secs(1),
+        |_| {},
+    ))
+    .unwrap_err();
+    assert!(e.to_string().contains("is smaller than"), "{}", e);
+    assert!(!dir.join("args").exists());
+}
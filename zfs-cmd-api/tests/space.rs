@@ -0,0 +1,14 @@
+extern crate zfs_cmd_api as zfs;
+
+use zfs::space::DestroyEstimate;
+
+#[test]
+fn destroy_estimate_parse() {
+    let e =
+        DestroyEstimate::parse("destroy\ttank/home@a\ndestroy\ttank/home@b\nreclaim\t1048576\n")
+            .unwrap();
+    assert_eq!(e.destroyed, ["tank/home@a", "tank/home@b"]);
+    assert_eq!(e.reclaim, 1048576);
+
+    assert!(DestroyEstimate::parse("would reclaim 1M\n").is_err());
+}
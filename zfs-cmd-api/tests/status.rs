@@ -0,0 +1,165 @@
+extern crate zfs_cmd_api as zfs;
+
+use std::time::{Duration, UNIX_EPOCH};
+use zfs::status::{parse_json, parse_scan_text, parse_text, ScanFunction, ScanState, VdevState};
+
+#[test]
+fn text() {
+    let pools = parse_text(include_str!("status.txt")).unwrap();
+    assert_eq!(pools.len(), 2);
+
+    let tank = &pools[0];
+    assert_eq!(tank.name, "tank");
+    assert_eq!(tank.state, VdevState::Degraded);
+    assert!(tank.status.as_ref().unwrap().contains("degraded state"));
+
+    let scan = tank.scan.as_ref().unwrap();
+    assert_eq!(scan.function, ScanFunction::Scrub);
+    assert_eq!(scan.state, ScanState::Scanning);
+    assert_eq!(scan.percent_done, Some(57.14));
+    assert_eq!(scan.eta, Some(Duration::from_secs(30 * 60)));
+    assert_eq!(scan.issued, Some(1 << 40));
+    assert_eq!(
+        scan.start_time,
+        Some(UNIX_EPOCH + Duration::from_secs(1696119841))
+    );
+    assert_eq!(scan.end_time, None);
+
+    let root = tank.root.as_ref().unwrap();
+    assert_eq!(root.name, "tank");
+    let mirror = &root.children[0];
+    assert_eq!(mirror.children.len(), 2);
+    assert_eq!(mirror.children[0].checksum_errors, 2);
+    assert_eq!(mirror.children[1].state, VdevState::Unavail);
+    assert_eq!(mirror.children[1].read_errors, 3);
+    assert_eq!(tank.logs[0].name, "nvme0n1");
+    assert_eq!(tank.spares[0].state, VdevState::Avail);
+    assert_eq!(tank.errors, ["/tank/data/file", "tank/data:<0x21>"]);
+
+    let boot = &pools[1];
+    assert_eq!(boot.state, VdevState::Online);
+    assert_eq!(boot.error_count, Some(0));
+    assert!(boot.errors.is_empty());
+    let scan = boot.scan.as_ref().unwrap();
+    assert_eq!(scan.state, ScanState::Finished);
+    assert_eq!(scan.errors, Some(0));
+    assert_eq!(
+        scan.end_time,
+        Some(UNIX_EPOCH + Duration::from_secs(1696724645))
+    );
+    assert_eq!(boot.root.as_ref().unwrap().walk().len(), 2);
+}
+
+#[test]
+fn scan_text() {
+    assert_eq!(parse_scan_text("none requested"), None);
+
+    let scan = parse_scan_text(
+        "resilver in progress since Sun Oct  1 00:24:01 2023\n\
+         1.23T scanned at 500M/s, 1.00T issued at 400M/s, 1.75T total\n\
+         512G resilvered, 57.14% done, 1 days 02:00:00 to go",
+    )
+    .unwrap();
+    assert_eq!(scan.function, ScanFunction::Resilver);
+    assert_eq!(scan.to_examine, Some(1924145348608));
+    assert_eq!(scan.eta, Some(Duration::from_secs(26 * 60 * 60)));
+}
+
+#[test]
+fn json() {
+    let json = r#"{
+      "output_version": {"command": "zpool status", "vers_major": 0, "vers_minor": 1},
+      "pools": {
+        "tank": {
+          "name": "tank",
+          "state": "ONLINE",
+          "pool_guid": "1",
+          "txg": "2",
+          "spa_version": "5000",
+          "zpl_version": "5",
+          "scan_stats": {
+            "function": "SCRUB",
+            "state": "SCANNING",
+            "start_time": "1000",
+            "end_time": "0",
+            "to_examine": "4000",
+            "examined": "3000",
+            "skipped": "0",
+            "processed": "0",
+            "errors": "0",
+            "bytes_per_scan": "3000",
+            "pass_start": "1000",
+            "scrub_pause": "-",
+            "scrub_spent_paused": "0",
+            "issued_bytes_per_scan": "1000",
+            "issued": "1000"
+          },
+          "vdevs": {
+            "tank": {
+              "name": "tank",
+              "vdev_type": "root",
+              "guid": "3",
+              "class": "normal",
+              "state": "ONLINE",
+              "read_errors": "0",
+              "write_errors": "0",
+              "checksum_errors": "0",
+              "vdevs": {
+                "sda": {
+                  "name": "sda",
+                  "vdev_type": "disk",
+                  "guid": "4",
+                  "path": "/dev/sda1",
+                  "class": "normal",
+                  "state": "ONLINE",
+                  "read_errors": "0",
+                  "write_errors": "0",
+                  "checksum_errors": "5"
+                }
+              }
+            }
+          },
+          "special": {
+            "nvme0n1": {
+              "name": "nvme0n1",
+              "vdev_type": "disk",
+              "guid": "5",
+              "path": "/dev/nvme0n1p1",
+              "class": "special",
+              "state": "ONLINE",
+              "read_errors": "0",
+              "write_errors": "0",
+              "checksum_errors": "0"
+            }
+          },
+          "error_count": "1",
+          "errors": {"/tank/file": {"path": "/tank/file"}}
+        }
+      }
+    }"#;
+
+    let now = UNIX_EPOCH + Duration::from_secs(1010);
+    let pools = parse_json(json.as_bytes(), now).unwrap();
+    let tank = &pools[0];
+    assert_eq!(tank.state, VdevState::Online);
+
+    let scan = tank.scan.as_ref().unwrap();
+    assert_eq!(
+        scan.start_time,
+        Some(UNIX_EPOCH + Duration::from_secs(1000))
+    );
+    assert_eq!(scan.end_time, None);
+    assert_eq!(scan.percent_done, Some(25.0));
+    // 1000 bytes in 10 seconds, 3000 to go
+    assert_eq!(scan.eta, Some(Duration::from_secs(30)));
+
+    let root = tank.root.as_ref().unwrap();
+    let sda = &root.children[0];
+    assert_eq!(sda.path.as_deref(), Some("/dev/sda1"));
+    assert_eq!(sda.checksum_errors, 5);
+    // the special class vdev follows the normal ones
+    assert_eq!(root.children.len(), 2);
+    assert_eq!(root.children[1].name, "nvme0n1");
+    assert_eq!(tank.error_count, Some(1));
+    assert_eq!(tank.errors, ["/tank/file"]);
+}
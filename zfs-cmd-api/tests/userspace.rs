@@ -0,0 +1,34 @@
+extern crate zfs_cmd_api as zfs;
+
+use zfs::userspace::{QuotaTarget, SpaceRow, SpaceType};
+
+#[test]
+fn parse_rows() {
+    let r = SpaceRow::parse_line("POSIX User\talice\t1048576\t10737418240\t12\tnone").unwrap();
+    assert_eq!(r.space_type, SpaceType::PosixUser);
+    assert_eq!(r.name, "alice");
+    assert_eq!(r.used, 1048576);
+    assert_eq!(r.quota, Some(10737418240));
+    assert_eq!(r.objused, Some(12));
+    assert_eq!(r.objquota, None);
+
+    let r = SpaceRow::parse_line("Project\t5\t4096\tnone\t-\tnone").unwrap();
+    assert_eq!(r.space_type, SpaceType::Project);
+    assert_eq!(r.objused, None);
+
+    assert!(SpaceRow::parse_line("POSIX User\talice\t1M\tnone\t1\tnone").is_err());
+    assert!(SpaceRow::parse_line("Nobody\talice\t1\tnone\t1\tnone").is_err());
+}
+
+#[test]
+fn quota_properties() {
+    assert_eq!(
+        QuotaTarget::User("alice").property(false),
+        "userquota@alice"
+    );
+    assert_eq!(
+        QuotaTarget::Group("staff").property(true),
+        "groupobjquota@staff"
+    );
+    assert_eq!(QuotaTarget::Project(5).property(false), "projectquota@5");
+}
@@ -0,0 +1,51 @@
+extern crate zfs_cmd_api as zfs;
+
+use zfs::value::{parse_percent, parse_ratio, parse_size, PropertyValue};
+use zfs::zpool::ZpoolList;
+
+#[test]
+fn sizes() {
+    assert_eq!(parse_size("512").unwrap(), Some(512));
+    assert_eq!(parse_size("512B").unwrap(), Some(512));
+    assert_eq!(parse_size("601G").unwrap(), Some(601 << 30));
+    assert_eq!(parse_size("1.75T").unwrap(), Some(1924145348608));
+    assert_eq!(parse_size("-").unwrap(), None);
+    assert!(parse_size("1.75Q").is_err());
+    assert!(parse_size("T").is_err());
+}
+
+#[test]
+fn percent_and_ratio() {
+    assert_eq!(parse_percent("33.6%").unwrap(), Some(33.6));
+    assert_eq!(parse_percent("33").unwrap(), Some(33.0));
+    assert_eq!(parse_ratio("1.00x").unwrap(), Some(1.0));
+    assert_eq!(parse_ratio("-").unwrap(), None);
+    assert!(parse_ratio("fast").is_err());
+}
+
+#[test]
+fn inferred() {
+    assert_eq!(PropertyValue::parse("-"), PropertyValue::None);
+    assert_eq!(PropertyValue::parse("20%"), PropertyValue::Percent(20.0));
+    assert_eq!(PropertyValue::parse("1.50x"), PropertyValue::Ratio(1.5));
+    assert_eq!(PropertyValue::parse("off"), PropertyValue::Bool(false));
+    assert_eq!(PropertyValue::parse("16K"), PropertyValue::Bytes(16384));
+    assert_eq!(PropertyValue::parse("4096"), PropertyValue::Number(4096));
+    assert_eq!(
+        PropertyValue::parse("ONLINE"),
+        PropertyValue::Text("ONLINE".to_owned())
+    );
+}
+
+#[test]
+fn zpool_list_properties() {
+    let list: ZpoolList = serde_json::from_str(include_str!("data.json")).unwrap();
+    let props = &list.pools["mainrust"].properties;
+
+    let size = props["size"].bytes().unwrap().unwrap();
+    let free = props["free"].bytes().unwrap().unwrap();
+    assert!(free < size);
+    assert_eq!(props["capacity"].percent().unwrap(), Some(33.0));
+    assert_eq!(props["dedupratio"].ratio().unwrap(), Some(1.0));
+    assert_eq!(props["checkpoint"].bytes().unwrap(), None);
+}
@@ -0,0 +1,75 @@
+extern crate zfs_cmd_api as zfs;
+
+use zfs::vdev::{parse_dry_run, VdevClass, VdevSpec, VdevType};
+
+#[test]
+fn spec_args() {
+    assert_eq!(
+        VdevSpec::mirror(&["sda", "sdb"]).args(),
+        ["mirror", "sda", "sdb"]
+    );
+    assert_eq!(
+        VdevSpec::raidz(2, &["a", "b", "c", "d"])
+            .class(VdevClass::Special)
+            .args(),
+        ["special", "raidz2", "a", "b", "c", "d"]
+    );
+    let draid = VdevType::Draid {
+        parity: 1,
+        data: Some(4),
+        children: None,
+        spares: Some(1),
+    };
+    assert_eq!(
+        VdevSpec::new(VdevClass::Normal, draid, &["a", "b"]).args(),
+        ["draid1:4d:1s", "a", "b"]
+    );
+    assert_eq!(
+        VdevSpec::disks(&["nvme0n1"]).class(VdevClass::Cache).args(),
+        ["cache", "nvme0n1"]
+    );
+}
+
+#[test]
+fn validate() {
+    assert!(VdevSpec::mirror(&["sda"]).validate().is_err());
+    assert!(VdevSpec::raidz(4, &["a", "b"]).validate().is_err());
+    assert!(VdevSpec::mirror(&["a", "b"])
+        .class(VdevClass::Spare)
+        .validate()
+        .is_err());
+    assert!(VdevSpec::mirror(&["a", "b"])
+        .class(VdevClass::Log)
+        .validate()
+        .is_ok());
+}
+
+#[test]
+fn dry_run() {
+    let layout = parse_dry_run(
+        "would update 'tank' to the following configuration:\n\
+         \n\
+         \ttank\n\
+         \t  mirror-0\n\
+         \t    sda\n\
+         \t    sdb\n\
+         \t  mirror-1\n\
+         \t    sdc\n\
+         \t    sdd\n\
+         \tlogs\n\
+         \t  mirror-2\n\
+         \t    nvme0n1\n\
+         \t    nvme1n1\n\
+         \tspares\n\
+         \t  sde\n",
+    )
+    .unwrap();
+
+    assert_eq!(layout.root.name, "tank");
+    assert_eq!(layout.root.children.len(), 2);
+    assert_eq!(layout.root.children[1].children[1].name, "sdd");
+    assert_eq!(layout.sections["logs"][0].children.len(), 2);
+    assert_eq!(layout.sections["spares"][0].name, "sde");
+
+    assert!(parse_dry_run("cannot open 'tank': no such pool\n").is_err());
+}
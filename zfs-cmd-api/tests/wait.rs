@@ -0,0 +1,65 @@
+extern crate zfs_cmd_api as zfs;
+
+use std::time::Duration;
+use zfs::wait::{
+    parse_progress, progress_columns, DatasetActivity, PoolActivity, WaitStatus, POOL_ACTIVITIES,
+};
+use zfs::{OpClass, Zfs};
+
+#[test]
+fn columns() {
+    assert_eq!(
+        progress_columns(&[PoolActivity::Scrub, PoolActivity::Free, PoolActivity::Scrub]),
+        [PoolActivity::Free, PoolActivity::Scrub]
+    );
+    assert_eq!(progress_columns(&[]), POOL_ACTIVITIES);
+}
+
+#[test]
+fn progress() {
+    let columns = [PoolActivity::Free, PoolActivity::Scrub];
+    assert_eq!(
+        parse_progress("1073741824\t0", &columns).unwrap(),
+        [(PoolActivity::Free, 1073741824), (PoolActivity::Scrub, 0)]
+    );
+    assert!(parse_progress("1G\t0", &columns).is_err());
+    assert!(parse_progress("0", &columns).is_err());
+}
+
+/// A `Zfs` whose `zfs wait` runs `script` instead
+fn zfs_running(script: &str) -> Zfs {
+    let mut zfs = Zfs::default();
+    zfs.prefix(
+        OpClass::Read,
+        vec!["sh".to_owned(), "-c".to_owned(), script.to_owned()],
+    );
+    zfs
+}
+
+#[test]
+fn dataset_wait_progress() {
+    let zfs = zfs_running("sleep 1.5");
+    let mut waited = Vec::new();
+    let status = zfs
+        .wait("tank/home", DatasetActivity::DeleteQueue, None, |d| {
+            waited.push(d)
+        })
+        .unwrap();
+    assert_eq!(status, WaitStatus::Complete);
+    assert_eq!(waited.len(), 1);
+    assert!(waited[0] >= Duration::from_secs(1));
+}
+
+#[test]
+fn dataset_wait_timeout() {
+    let zfs = zfs_running("sleep 10");
+    let status = zfs
+        .wait(
+            "tank/home",
+            DatasetActivity::DeleteQueue,
+            Some(Duration::from_millis(200)),
+            |_| {},
+        )
+        .unwrap();
+    assert_eq!(status, WaitStatus::TimedOut);
+}
@@ -5,6 +5,49 @@ fn zfs() {
     let _ = zfs::Zfs::default();
 }
 
+#[test]
+fn is_remote() {
+    let zfs = |cmd: &str| {
+        let var = format!(
+            "REMOTE_{}",
+            cmd.replace(|c: char| !c.is_alphanumeric(), "_")
+        );
+        std::env::set_var(format!("{}_ZFS_CMD", var), cmd);
+        zfs::Zfs::from_env_prefix(&var)
+    };
+
+    assert!(!zfs("zfs").is_remote());
+    assert!(!zfs("sudo zfs").is_remote());
+    assert!(!zfs("sudo -n /sbin/zfs").is_remote());
+    assert!(zfs("ssh backup zfs").is_remote());
+    assert!(zfs("/usr/bin/ssh -p 2222 root@backup zfs").is_remote());
+    // nothing but `ssh` would be run here, which is a bad zfs command but not a remote one
+    assert!(!zfs("ssh").is_remote());
+}
+
+#[test]
+fn host_cmd() {
+    let zfs = |cmd: &str| {
+        let var = format!("HOST_{}", cmd.replace(|c: char| !c.is_alphanumeric(), "_"));
+        std::env::set_var(format!("{}_ZFS_CMD", var), cmd);
+        let mut zfs = zfs::Zfs::from_env_prefix(&var);
+        zfs.prefix(zfs::OpClass::Mutate, vec!["sudo".to_owned()]);
+        zfs
+    };
+    let words = |cmd: std::process::Command| {
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|w| w.to_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(words(zfs("zfs").host_cmd("id")), ["id"]);
+    assert_eq!(
+        words(zfs("ssh -p 2222 backup@host zfs").host_cmd("id")),
+        ["ssh", "-p", "2222", "backup@host", "id"]
+    );
+}
+
 #[test]
 fn zfs_list() {
     let zfs = zfs::Zfs::default();
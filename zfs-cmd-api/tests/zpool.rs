@@ -0,0 +1,95 @@
+extern crate zfs_cmd_api as zfs;
+
+use zfs::status::VdevState;
+use zfs::value::FeatureState;
+use zfs::zpool::{parse_get, PoolProperties, ZpoolList};
+
+fn sample() -> ZpoolList {
+    serde_json::from_str(include_str!("data.json")).unwrap()
+}
+
+#[test]
+fn list_pools() {
+    let list = sample();
+    assert_eq!(list.output_version.command, "zpool list");
+    assert_eq!(list.pools.keys().collect::<Vec<_>>(), ["mainrust", "tank"]);
+
+    let pool = &list.pools["mainrust"];
+    assert_eq!(pool.state, "ONLINE");
+    assert_eq!(pool.pool_guid, "13666012711349147706");
+    assert_eq!(pool.properties["capacity"].value, "33%");
+    assert_eq!(pool.properties["altroot"].source.r#type, "DEFAULT");
+}
+
+#[test]
+fn list_vdevs() {
+    let list = sample();
+
+    let mirror = &list.pools["mainrust"].vdevs["mirror-0"];
+    assert_eq!(mirror.vdev_type, "mirror");
+    assert_eq!(mirror.path, None);
+    assert_eq!(mirror.vdevs.len(), 2);
+    assert!(mirror.vdevs.values().all(|v| v.is_leaf()));
+
+    // nested: mirror-3 contains a replacing vdev
+    let tank = &list.pools["tank"];
+    let mirror = &tank.vdevs["mirror-3"];
+    let replacing = &mirror.vdevs["replacing-1"];
+    assert_eq!(replacing.vdev_type, "replacing");
+    assert_eq!(
+        replacing.vdevs["i01"].path.as_deref(),
+        Some("/dev/mapper/i01")
+    );
+    assert_eq!(
+        replacing.vdevs["dm-name-z8.2"].devid.as_deref(),
+        Some("dm-uuid-CRYPT-LUKS1-d353aef34afa4f8a9f604c233dd3feae-z8.2")
+    );
+
+    assert_eq!(mirror.walk().len(), 5);
+    let leaves = tank
+        .vdevs
+        .values()
+        .flat_map(|v| v.walk())
+        .filter(|v| v.is_leaf())
+        .count();
+    assert_eq!(leaves, 23);
+}
+
+#[test]
+fn typed_properties() {
+    let list = sample();
+    let props = list.pools["mainrust"].typed_properties().unwrap();
+
+    assert_eq!(props.allocated, Some(601 << 30));
+    assert_eq!(props.capacity, Some(33.0));
+    assert_eq!(props.fragmentation, Some(20.0));
+    assert_eq!(props.dedupratio, Some(1.0));
+    assert_eq!(props.health, Some(VdevState::Online));
+    assert_eq!(props.altroot, None);
+    assert_eq!(props.checkpoint, None);
+}
+
+#[test]
+fn get_text() {
+    let props = parse_get(
+        "size\t1924145348608\t-\n\
+         altroot\t/mnt\tlocal\n\
+         feature@async_destroy\tenabled\tlocal\n\
+         feature@encryption\tactive\tlocal\n",
+    )
+    .unwrap();
+
+    assert_eq!(props["size"].source.r#type, "NONE");
+    assert_eq!(props["altroot"].source.r#type, "LOCAL");
+    assert_eq!(
+        props["feature@encryption"].feature().unwrap(),
+        Some(FeatureState::Active)
+    );
+    assert!(props["size"].feature().is_err());
+
+    let typed = PoolProperties::from_map(&props).unwrap();
+    assert_eq!(typed.size, Some(1924145348608));
+    assert_eq!(typed.altroot.as_deref(), Some("/mnt"));
+
+    assert!(parse_get("size\t1\n").is_err());
+}
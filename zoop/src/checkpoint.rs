@@ -0,0 +1,49 @@
+//! Checkpoint the destination pool before a run which may destroy or roll back data on it (forced
+//! receives, pruning), so a run that goes wrong can be undone by rewinding the pool.
+//!
+//! The checkpoint is only discarded once the run succeeded and was verified. Otherwise it is
+//! kept, and rewinding to it (or discarding it) is left to the administrator.
+
+use zfs_cmd_api::maintenance::CheckpointAction;
+use zfs_cmd_api::zpool::ZpoolCmd;
+
+/// Checkpoint `pool`, call `run`, and discard the checkpoint if `run` succeeds.
+///
+/// Fails without calling `run` if `pool` already has a checkpoint: it may be someone else's
+/// safety net, and a pool can only have one.
+pub async fn guarded<F>(zpool: &ZpoolCmd, pool: &str, run: F) -> Result<(), String>
+where
+    F: FnOnce() -> Result<(), String>,
+{
+    let existing = zpool
+        .checkpoint_size(pool)
+        .await
+        .map_err(|e| format!("could not check for a checkpoint of {}: {}", pool, e))?;
+    if let Some(size) = existing {
+        return Err(format!(
+            "{} already has a checkpoint ({} bytes); rewind to it or discard it (`zpool checkpoint -d {}`) first",
+            pool, size, pool
+        ));
+    }
+
+    zpool
+        .checkpoint(pool, CheckpointAction::Create)
+        .await
+        .map_err(|e| format!("could not checkpoint {}: {}", pool, e))?;
+    println!("checkpoint: created checkpoint of {}", pool);
+
+    if let Err(e) = run() {
+        eprintln!(
+            "checkpoint: keeping checkpoint of {}. To undo this run, `zpool export {}` and `zpool import --rewind-to-checkpoint {}`; to keep it, `zpool checkpoint -d {}`",
+            pool, pool, pool, pool
+        );
+        return Err(e);
+    }
+
+    zpool
+        .checkpoint(pool, CheckpointAction::Discard { wait: false })
+        .await
+        .map_err(|e| format!("could not discard checkpoint of {}: {}", pool, e))?;
+    println!("checkpoint: discarded checkpoint of {}", pool);
+    Ok(())
+}
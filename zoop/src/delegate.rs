@@ -0,0 +1,190 @@
+//! Delegate (with `zfs allow`) the permissions needed to run `zcopy` as an unprivileged user.
+
+use zfs_cmd_api::allow::{Scope, Who};
+use zfs_cmd_api::{Zfs, ZfsError};
+
+/// Permissions `zcopy` needs on the source dataset tree
+pub const SRC_PERMISSIONS: &[&str] = &["send", "hold", "bookmark"];
+
+/// Permissions `zcopy` needs on the destination dataset tree
+///
+/// `destroy` is needed because `recv -F` removes snapshots that no longer exist on the source,
+/// and because `zcopy` removes destination snapshots when no basis for an incremental is found.
+pub const DEST_PERMISSIONS: &[&str] = &["receive", "create", "mount", "destroy"];
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MissingPermissions {
+    pub user: String,
+    pub dataset: String,
+    pub permissions: Vec<String>,
+}
+
+/// Run `id` with `args` on the host `zfs` runs on, returning its output
+fn id(zfs: &Zfs, args: &[&str]) -> Option<String> {
+    match zfs.host_cmd("id").args(args).output() {
+        Ok(output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        }
+        _ => None,
+    }
+}
+
+/// The user `zfs` runs as, as reported by `id -un` on the host it runs on
+pub fn current_user(zfs: &Zfs) -> Option<String> {
+    id(zfs, &["-un"]).filter(|u| !u.is_empty())
+}
+
+/// Groups `user` is a member of, as reported by `id -Gn` on the host `zfs` runs on
+pub fn user_groups(zfs: &Zfs, user: &str) -> Vec<String> {
+    id(zfs, &["-Gn", user])
+        .map(|groups| groups.split_whitespace().map(|g| g.to_owned()).collect())
+        .unwrap_or_default()
+}
+
+fn missing_on(
+    zfs: &Zfs,
+    user: &str,
+    groups: &[&str],
+    dataset: &str,
+    required: &[&str],
+) -> Result<Option<MissingPermissions>, ZfsError> {
+    let effective = zfs.allowed(dataset)?.effective(dataset, user, groups);
+    let permissions: Vec<String> = required
+        .iter()
+        .filter(|p| !effective.contains(**p))
+        .map(|p| (*p).to_owned())
+        .collect();
+
+    if permissions.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(MissingPermissions {
+            user: user.to_owned(),
+            dataset: dataset.to_owned(),
+            permissions,
+        }))
+    }
+}
+
+/// Determine which permissions `user` lacks on `dataset`, or, if it does not exist (`zcopy`
+/// creates it), on its nearest existing ancestor.
+fn missing_on_nearest(
+    zfs: &Zfs,
+    user: &str,
+    groups: &[&str],
+    dataset: &str,
+    required: &[&str],
+) -> Result<(String, Option<MissingPermissions>), String> {
+    let mut nearest = dataset;
+    loop {
+        match missing_on(zfs, user, groups, nearest, required) {
+            Ok(m) => return Ok((nearest.to_owned(), m)),
+            Err(ZfsError::NoDataset { .. }) => match nearest.rfind('/') {
+                Some(i) => nearest = &nearest[..i],
+                None => return Err(format!("no ancestor of {} exists", dataset)),
+            },
+            Err(e) => {
+                return Err(format!("could not check permissions on {}: {}", nearest, e));
+            }
+        }
+    }
+}
+
+/// Determine which permissions `user` lacks to `zcopy` from `src_dataset` to `dest_dataset`.
+/// Without a `user`, the user each side's `zfs` runs as is examined. Root needs no delegated
+/// permissions, so a side accessed as root is skipped.
+///
+/// Groups are looked up on the host each side's `zfs` runs on. As `zcopy` creates
+/// `dest_dataset` if needed, when it does not exist the nearest existing ancestor is examined
+/// instead.
+pub fn check_delegation(
+    src_zfs: &Zfs,
+    dest_zfs: &Zfs,
+    user: Option<&str>,
+    src_dataset: &str,
+    dest_dataset: &str,
+) -> Result<Vec<MissingPermissions>, String> {
+    // the user each side is checked for, with its groups, or `None` to skip it
+    let user_on = |zfs: &Zfs, dataset: &str| -> Result<Option<(String, Vec<String>)>, String> {
+        let user = match user {
+            Some(u) => u.to_owned(),
+            None => current_user(zfs).ok_or_else(|| {
+                format!("could not determine the user {} is accessed as", dataset)
+            })?,
+        };
+        if user == "root" {
+            return Ok(None);
+        }
+        let groups = user_groups(zfs, &user);
+        Ok(Some((user, groups)))
+    };
+    let mut missing = Vec::new();
+
+    if let Some((user, groups)) = user_on(src_zfs, src_dataset)? {
+        let groups: Vec<&str> = groups.iter().map(|g| g.as_str()).collect();
+        missing.extend(
+            missing_on(src_zfs, &user, &groups, src_dataset, SRC_PERMISSIONS)
+                .map_err(|e| format!("could not check permissions on {}: {}", src_dataset, e))?,
+        );
+    }
+    if let Some((user, groups)) = user_on(dest_zfs, dest_dataset)? {
+        let groups: Vec<&str> = groups.iter().map(|g| g.as_str()).collect();
+        missing.extend(
+            missing_on_nearest(dest_zfs, &user, &groups, dest_dataset, DEST_PERMISSIONS)?.1,
+        );
+    }
+
+    Ok(missing)
+}
+
+/// Grant `user` the permissions it lacks to `zcopy` from `src_dataset` to `dest_dataset`.
+///
+/// `src_dataset` must already exist. If `dest_dataset` does not, permissions are granted on its
+/// nearest existing ancestor instead, as they are on both datasets' descendants.
+pub fn delegate(
+    src_zfs: &Zfs,
+    dest_zfs: &Zfs,
+    dry_run: bool,
+    user: &str,
+    src_dataset: &str,
+    dest_dataset: &str,
+) -> Result<(), String> {
+    let src_groups = user_groups(src_zfs, user);
+    let src_groups: Vec<&str> = src_groups.iter().map(|g| g.as_str()).collect();
+    let dest_groups = user_groups(dest_zfs, user);
+    let dest_groups: Vec<&str> = dest_groups.iter().map(|g| g.as_str()).collect();
+
+    let src_missing = missing_on(src_zfs, user, &src_groups, src_dataset, SRC_PERMISSIONS)
+        .map_err(|e| format!("could not check permissions on {}: {}", src_dataset, e))?;
+    let (dest_nearest, dest_missing) =
+        missing_on_nearest(dest_zfs, user, &dest_groups, dest_dataset, DEST_PERMISSIONS)?;
+
+    for (zfs, dataset, required, missing) in [
+        (src_zfs, src_dataset, SRC_PERMISSIONS, src_missing),
+        (dest_zfs, &dest_nearest[..], DEST_PERMISSIONS, dest_missing),
+    ] {
+        let missing = match missing {
+            Some(m) => m,
+            None => {
+                println!("{}: {} already has {}", dataset, user, required.join(","));
+                continue;
+            }
+        };
+
+        let permissions: Vec<&str> = missing.permissions.iter().map(|p| p.as_str()).collect();
+        println!("{}: allow {} {}", dataset, user, permissions.join(","));
+        if dry_run {
+            continue;
+        }
+
+        zfs.allow(
+            dataset,
+            &Who::User(user.to_owned()),
+            Scope::LocalAndDescendent,
+            &permissions,
+        )
+        .map_err(|e| format!("could not delegate permissions on {}: {}", dataset, e))?;
+    }
+
+    Ok(())
+}
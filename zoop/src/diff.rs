@@ -0,0 +1,175 @@
+//! Summarize what changed between the two most recently replicated snapshots.
+
+use std::collections::BTreeSet;
+use zfs_cmd_api::diff::ChangeType;
+use zfs_cmd_api::{ListBuilder, Zfs, ZfsError};
+
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct DiffSummary {
+    pub created: u64,
+    pub removed: u64,
+    pub modified: u64,
+    pub renamed: u64,
+}
+
+fn list_snapshots(zfs: &Zfs, dataset: &str) -> Result<Vec<Vec<String>>, ZfsError> {
+    let mut builder = ListBuilder::default();
+    builder
+        .include_snapshots()
+        .depth(1)
+        .with_elements(&["createtxg", "name", "guid"])
+        .with_dataset(dataset);
+
+    Ok(From::from(&zfs.list_from_builder(&builder)?))
+}
+
+/// Snapshots of `src_dataset` which have been replicated to `dest_dataset` (identified by
+/// `guid`), oldest first.
+pub fn replicated_snapshots(
+    src_zfs: &Zfs,
+    dest_zfs: &Zfs,
+    src_dataset: &str,
+    dest_dataset: &str,
+) -> Result<Vec<String>, String> {
+    let dest_guids: BTreeSet<String> = match list_snapshots(dest_zfs, dest_dataset) {
+        Ok(rows) => rows.into_iter().map(|mut r| r.swap_remove(2)).collect(),
+        Err(ZfsError::NoDataset { .. }) => BTreeSet::new(),
+        Err(e) => return Err(format!("dst list failed: {}", e)),
+    };
+
+    let mut src: Vec<(u64, String)> = Vec::new();
+    for row in
+        list_snapshots(src_zfs, src_dataset).map_err(|e| format!("src list failed: {}", e))?
+    {
+        if !dest_guids.contains(&row[2]) {
+            continue;
+        }
+        let createtxg = row[0]
+            .parse()
+            .map_err(|e| format!("bad createtxg {:?}: {}", row[0], e))?;
+        src.push((createtxg, row[1].clone()));
+    }
+
+    src.sort();
+    Ok(src.into_iter().map(|(_, name)| name).collect())
+}
+
+/// Check that the newest snapshot of `src_dataset` (and, if `recursive`, of each filesystem
+/// beneath it) has been replicated to the matching dataset beneath `dest_dataset`.
+pub fn verify_replicated(
+    src_zfs: &Zfs,
+    dest_zfs: &Zfs,
+    recursive: bool,
+    src_dataset: &str,
+    dest_dataset: &str,
+) -> Result<(), String> {
+    let mut datasets = vec![src_dataset.to_owned()];
+    if recursive {
+        let mut builder = ListBuilder::default();
+        builder
+            .include_filesystems()
+            .recursive()
+            .with_elements(&["name"])
+            .with_dataset(src_dataset);
+        let rows: Vec<Vec<String>> = From::from(
+            &src_zfs
+                .list_from_builder(&builder)
+                .map_err(|e| format!("src list failed: {}", e))?,
+        );
+        datasets = rows.into_iter().map(|mut r| r.swap_remove(0)).collect();
+    }
+
+    let mut missing = Vec::new();
+    for this_src_ds in datasets.iter() {
+        let this_dest_ds = format!("{}{}", dest_dataset, &this_src_ds[src_dataset.len()..]);
+
+        let mut newest: Option<(u64, String)> = None;
+        for row in
+            list_snapshots(src_zfs, this_src_ds).map_err(|e| format!("src list failed: {}", e))?
+        {
+            let createtxg: u64 = row[0]
+                .parse()
+                .map_err(|e| format!("bad createtxg {:?}: {}", row[0], e))?;
+            let is_newer = match newest {
+                Some((txg, _)) => createtxg > txg,
+                None => true,
+            };
+            if is_newer {
+                newest = Some((createtxg, row[1].clone()));
+            }
+        }
+        let newest = match newest {
+            Some((_, name)) => name,
+            // nothing to replicate
+            None => continue,
+        };
+
+        let replicated = replicated_snapshots(src_zfs, dest_zfs, this_src_ds, &this_dest_ds)?;
+        if replicated.last() != Some(&newest) {
+            missing.push(format!("{} is not on {}", newest, this_dest_ds));
+        }
+    }
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(missing.join(", "))
+    }
+}
+
+/// Print a summary of the changes (on the source) between the last two snapshots replicated from
+/// `src_dataset` to `dest_dataset`. If `verbose`, each change is printed as well.
+pub fn diff_last_replicated(
+    src_zfs: &Zfs,
+    dest_zfs: &Zfs,
+    verbose: bool,
+    src_dataset: &str,
+    dest_dataset: &str,
+) -> Result<DiffSummary, String> {
+    let snaps = replicated_snapshots(src_zfs, dest_zfs, src_dataset, dest_dataset)?;
+    if snaps.len() < 2 {
+        return Err(format!(
+            "{} has {} snapshot(s) replicated to {}, need 2 to diff",
+            src_dataset,
+            snaps.len(),
+            dest_dataset
+        ));
+    }
+
+    let from = &snaps[snaps.len() - 2];
+    let to = &snaps[snaps.len() - 1];
+    println!("diff {} {}", from, to);
+
+    let mut summary = DiffSummary::default();
+    for record in src_zfs
+        .diff(from, to)
+        .map_err(|e| format!("diff failed: {}", e))?
+    {
+        let record = record.map_err(|e| format!("diff failed: {}", e))?;
+        match record.change {
+            ChangeType::Created => summary.created += 1,
+            ChangeType::Removed => summary.removed += 1,
+            ChangeType::Modified => summary.modified += 1,
+            ChangeType::Renamed => summary.renamed += 1,
+        }
+
+        if verbose {
+            match record.new_path {
+                Some(ref new_path) => println!(
+                    " {:?} {} -> {}",
+                    record.change,
+                    record.path.display(),
+                    new_path.display()
+                ),
+                None => println!(" {:?} {}", record.change, record.path.display()),
+            }
+        }
+    }
+
+    println!(
+        " created: {}, removed: {}, modified: {}, renamed: {}",
+        summary.created, summary.removed, summary.modified, summary.renamed
+    );
+
+    Ok(summary)
+}
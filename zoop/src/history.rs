@@ -0,0 +1,54 @@
+//! Report what `zpool history` recorded about a dataset or snapshot, such as when (and by which
+//! command) a snapshot an incremental or resumed send depends on was destroyed.
+
+use crate::lock::pool_of;
+use zfs_cmd_api::history::{filter, format_time, HistoryEvent};
+use zfs_cmd_api::zpool::ZpoolCmd;
+
+/// Print the history records mentioning `dataset` (including its descendants, snapshots, and
+/// bookmarks), oldest first
+pub async fn report(zpool: &ZpoolCmd, dataset: &str) -> Result<(), String> {
+    let pool = pool_of(dataset);
+    let records = zpool
+        .history(pool)
+        .await
+        .map_err(|e| format!("could not get history of {}: {}", pool, e))?;
+
+    for r in filter(&records, Some(dataset), None, None) {
+        let what = match r.event {
+            HistoryEvent::Command(ref cmd) => cmd.clone(),
+            HistoryEvent::Internal {
+                ref event,
+                ref dataset,
+                ref detail,
+                ..
+            } => format!(
+                "[txg:{}] {} {} {}",
+                r.txg.unwrap_or_default(),
+                event,
+                dataset.as_deref().unwrap_or("-"),
+                detail
+            ),
+            HistoryEvent::Ioctl { ref name, .. } => format!("ioctl {}", name),
+        };
+
+        let mut by = Vec::new();
+        match (r.user.as_ref(), r.uid) {
+            (Some(user), _) => by.push(format!("user {}", user)),
+            (None, Some(uid)) => by.push(format!("uid {}", uid)),
+            (None, None) => {}
+        }
+        if let Some(ref host) = r.host {
+            by.push(format!("on {}", host));
+        }
+
+        println!(
+            "{} {} [{}]",
+            format_time(r.time),
+            what.trim_end(),
+            by.join(" ")
+        );
+    }
+
+    Ok(())
+}
@@ -26,6 +26,15 @@ use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::error::Error;
 
+pub mod checkpoint;
+pub mod delegate;
+pub mod diff;
+pub mod history;
+pub mod lock;
+pub mod remount;
+pub mod rotate;
+pub mod scrub;
+
 #[derive(Debug,PartialEq,Eq,PartialOrd,Ord,Hash,Clone)]
 enum DatasetType {
     Snapshot,
@@ -94,7 +103,7 @@ impl From<&str> for Guid {
 
 impl From<String> for Guid {
     fn from(s: String) -> Self {
-        Guid { s: s }
+        Guid { s }
     }
 }
 
@@ -136,10 +145,10 @@ pub struct TrimPoint {
     pub offset: u64,
 }
 
-/// 1. must be sorted by increasing `offset` (smallest first)
-/// 2. the `density` also end up sorted in decreasing (largest first) order.
-///
-/// Note: duplicate offsets and densities likely need handling.
+// 1. must be sorted by increasing `offset` (smallest first)
+// 2. the `density` also end up sorted in decreasing (largest first) order.
+//
+// Note: duplicate offsets and densities likely need handling.
 /*
 fn validate_trim_points(trim_points: &[TrimPoint]) -> Result<(),()>
 {
@@ -196,8 +205,28 @@ impl Default for ZcopyOpts {
     }
 }
 
+/// Keep `zoop scrub` from starting scrubs on the pools of `src_dataset` and `dest_dataset` while
+/// the returned locks are held
+fn replication_locks(opts: &ZcopyOpts, src_dataset: &str, dest_dataset: &str) -> Vec<lock::PoolLock>
+{
+    if opts.dry_run {
+        return Vec::new();
+    }
+
+    match lock::replicating(&[src_dataset, dest_dataset]) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("WARNING: could not lock pools for replication: {}", e);
+            Vec::new()
+        }
+    }
+}
+
 pub fn zcopy_recursive(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts, src_dataset: &str, dest_dataset: &str) -> Result<(), Vec<Box<dyn Error>>>
 {
+    // held across every dataset, so a scrub can't start between them
+    let _locks = replication_locks(opts, src_dataset, dest_dataset);
+
     // XXX: consider if it would be useful to obtain additional info other than name here.
     // XXX: should we match up these src filesystems with dest filesystems?
     let mut enum_ds = zfs_cmd_api::ListBuilder::default();
@@ -206,8 +235,9 @@ pub fn zcopy_recursive(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts, src_data
         .with_elements(&["name"])
         .with_dataset(src_dataset);
 
-    let dss = src_zfs.list_from_builder(&enum_ds)
-        .expect("could not enumerate decendent filesystems");
+    let dss = src_zfs.list_from_builder(&enum_ds).map_err(|e| {
+        vec![From::from(format!("could not enumerate decendent filesystems: {}", e))]
+    })?;
 
     let dss: BTreeSet<Vec<u8>> = dss.iter().map(|v| v.to_owned()).collect();
 
@@ -225,7 +255,7 @@ pub fn zcopy_recursive(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts, src_data
         let this_dest_ds = format!("{}{}", dest_dataset, ds_suffix);
 
 
-        match zcopy_one(src_zfs, dest_zfs, opts, this_src_ds, this_dest_ds.as_ref()) {
+        match zcopy_dataset(src_zfs, dest_zfs, opts, this_src_ds, this_dest_ds.as_ref()) {
             Ok(_) => {},
             Err(e) => {
                 error!("Error: zcopy {} to {} failed: {}", this_src_ds, this_dest_ds, e);
@@ -234,7 +264,7 @@ pub fn zcopy_recursive(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts, src_data
         }
     }
 
-    if errors.len() != 0  {
+    if !errors.is_empty()  {
         Err(errors)
     } else {
         Ok(())
@@ -251,6 +281,14 @@ fn show_zcopy(src_dataset: &str, dest_dataset: &str, shown: &mut bool)
 
 pub fn zcopy_one(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
         src_dataset: &str, dest_dataset: &str) -> Result<(), String>
+{
+    let _locks = replication_locks(opts, src_dataset, dest_dataset);
+    zcopy_dataset(src_zfs, dest_zfs, opts, src_dataset, dest_dataset)
+}
+
+/// `zcopy_one()`, with the caller holding the replication locks
+fn zcopy_dataset(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
+        src_dataset: &str, dest_dataset: &str) -> Result<(), String>
 {
     let mut shown = false;
     let mut get_receive_resume_token = zfs_cmd_api::ListBuilder::default();
@@ -303,7 +341,6 @@ pub fn zcopy_one(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
     // Problems:
     //  - need to transfer and manage entire list of snapshots. Will this grow too large?
 
-
     // determine if dataset has a partial receive, and resume it before proceeding with
     // normal incrimental send
     //
@@ -329,8 +366,12 @@ pub fn zcopy_one(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
 
                     show_zcopy(src_dataset, dest_dataset, &mut shown);
                     eprintln!("Resuming partial recv in {}", dest_dataset);
-                    let send = src_zfs.send_resume(res, send_flags).unwrap();
-                    let recv = dest_zfs.recv(dest_dataset, &[], None, &[], recv_flags).unwrap();
+                    let send = src_zfs.send_resume(res, send_flags).map_err(|e| {
+                        format!("resume send failed: {}", e)
+                    })?;
+                    let recv = dest_zfs.recv(dest_dataset, &[], None, &[], recv_flags).map_err(|e| {
+                        format!("recv failed: {}", e)
+                    })?;
 
                     // neither src nor dst are encrypted, but the error:
                     // > zoop[102113]: cannot receive incremental stream:
@@ -338,18 +379,17 @@ pub fn zcopy_one(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
                     // is emitted during a recv of a resumed send.
                     //
                     // seems plausible that we've got something not-quite-right going on.
-                    match zfs_cmd_api::send_recv(send, recv) {
-                        Err(e) => {
-                            // assume we've got a resume that starts from a non-existent snap.
-                            // try to abort the resume
-                            eprintln!("partial recv in '{}' could not be resumed, aborting: {:?}", dest_dataset, e);
-                            if !opts.dry_run {
-                                dest_zfs.recv_abort_incomplete(dest_dataset).unwrap();
-                            } else {
-                                eprintln!("skipping abort in dry run");
-                            }
-                        },
-                        Ok(_) => {}
+                    if let Err(e) = zfs_cmd_api::send_recv(send, recv) {
+                        // assume we've got a resume that starts from a non-existent snap.
+                        // try to abort the resume
+                        eprintln!("partial recv in '{}' could not be resumed, aborting: {:?}", dest_dataset, e);
+                        if !opts.dry_run {
+                            dest_zfs.recv_abort_incomplete(dest_dataset).map_err(|e| {
+                                format!("aborting partial recv failed: {}", e)
+                            })?;
+                        } else {
+                            eprintln!("skipping abort in dry run");
+                        }
                     }
                 }
             },
@@ -358,7 +398,9 @@ pub fn zcopy_one(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
                     eprintln!("filesystem {} does not exist, not resuming", dest_dataset);
                 }
             },
-            Err(e) => panic!("dst list failed: {}", e),
+            Err(e) => {
+                return Err(format!("dst list failed: {}", e));
+            }
         }
     }
 
@@ -438,19 +480,16 @@ pub fn zcopy_one(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
     for src_ds in src_dss.into_iter() {
         let dst = dst_guid_map.remove(&src_ds.guid).map(|x| x.ds);
         let k = (src_ds.ds.createtxg.clone(), src_ds.guid.clone());
-        match merged_dss.insert(k,
+        if let Some(x) = merged_dss.insert(k,
                 GlobalDataset {
                     guid: src_ds.guid,
                     src: src_ds.ds,
-                    dst: dst,
+                    dst,
                 }
             ){
-            Some(x) => {
-                // continue in a duplicate key case, but warn. This should never happen due to
-                // our use of the guid as a piece of the key.
-                eprintln!("WARNING: duplicate key: {:?}", x)
-            },
-            None => {},
+            // continue in a duplicate key case, but warn. This should never happen due to
+            // our use of the guid as a piece of the key.
+            eprintln!("WARNING: duplicate key: {:?}", x)
         }
     }
 
@@ -589,17 +628,29 @@ pub fn zcopy_one(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
                     .with_dataset(dest_dataset)
                     .include_snapshots()
                     .depth(1)
-                    .with_elements(&["name"]);
+                    .with_elements(&["createtxg", "name"]);
 
                 match dest_zfs.list_from_builder(&enum_ds_snaps) {
                     Ok(snaps) => {
                         // TODO: consider making this optional as it is technically a data loser.
-                        for snap in snaps.iter() {
-                            // XXX: are dataset/snap names always utf8?
-                            let snap = std::str::from_utf8(snap).unwrap();
-                            dest_zfs.destroy(destroy_flags, snap).map_err(|e| {
-                                format!("could not destroy snap {}: {}", snap, e)
+                        let mut snaps: Vec<Vec<String>> = From::from(&snaps);
+                        snaps.sort_by_key(|row| CreateTxg::from(&row[0][..]));
+
+                        // destroy them all at once, in createtxg order so they collapse into a
+                        // single range
+                        let names: Vec<&str> = snaps.iter().map(|row| {
+                            &row[1][row[1].find('@').unwrap() + 1..]
+                        }).collect();
+                        let report = dest_zfs.destroy_snapshots(destroy_flags, dest_dataset, &names, &names)
+                            .map_err(|e| {
+                                format!("could not destroy snaps of {}: {}", dest_dataset, e)
                             })?;
+
+                        if !report.failed.is_empty() {
+                            let failed: Vec<String> = report.failed.iter().map(|f| {
+                                format!("{} ({})", f.snapshot, f.reason)
+                            }).collect();
+                            return Err(format!("could not destroy snaps: {}", failed.join(", ")));
                         }
                     },
                     Err(e) => {
@@ -641,6 +692,9 @@ pub fn zcopy_one(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
 
     let mut shown_basis = false;
     let mut prev_dst_ds: Option<String> = None;
+    // filesystems mounted under `dest_dataset` block a full (no basis) `recv -F` over it.
+    // unmount them before that recv and restore them once we're done
+    let mut unmounted: Option<remount::Unmounted<'_>> = None;
 
     for (_, ds) in dss_iter {
         if opts.verbose {
@@ -668,12 +722,22 @@ pub fn zcopy_one(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
                     }
                     shown_basis = true;
                 }
+                if prev_dst_ds.is_none() && recv_flags.contains(zfs_cmd_api::RecvFlags::Force) && !opts.dry_run {
+                    unmounted = Some(remount::unmount_descendants(dest_zfs, dest_dataset)?);
+                }
                 println!(" sending {}", &ds.src.name[..]);
                 // send it
-                let send = src_zfs.send(&ds.src.name[..], prev_dst_ds.as_ref().map(|x| &**x), send_flags).unwrap();
-                let recv = dest_zfs.recv(dest_dataset, &[], None, &[], recv_flags).unwrap();
-
-                zfs_cmd_api::send_recv(send, recv).unwrap();
+                let send = src_zfs.send(&ds.src.name[..], prev_dst_ds.as_deref(), send_flags).map_err(|e| {
+                    format!("send of {} failed: {}", ds.src.name, e)
+                })?;
+                let recv = dest_zfs.recv(dest_dataset, &[], None, &[], recv_flags).map_err(|e| {
+                    format!("recv failed: {}", e)
+                })?;
+
+                // a disk pulled mid-transfer shows up here, and must not take the process down
+                zfs_cmd_api::send_recv(send, recv).map_err(|e| {
+                    format!("send/recv of {} failed: {}", ds.src.name, e)
+                })?;
 
                 // use as prev after send/recv finishes
             },
@@ -686,6 +750,10 @@ pub fn zcopy_one(src_zfs: &Zfs, dest_zfs: &Zfs, opts: &ZcopyOpts,
         prev_dst_ds = Some(ds.src.name.clone());
     }
 
+    if let Some(unmounted) = unmounted {
+        unmounted.restore()?;
+    }
+
     Ok(())
 
     // XXX: bookmarks on the SRC allow deletion of snapshots while still keeping send
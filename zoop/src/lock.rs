@@ -0,0 +1,64 @@
+//! Per-pool advisory locks, letting pool maintenance (`zoop scrub`) avoid pools a `zcopy` is
+//! replicating to or from.
+//!
+//! Replication holds shared locks, so any number of `zcopy`s may run at once, while maintenance
+//! only proceeds if it can take an exclusive lock. These only coordinate `zoop` processes on this
+//! host.
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::PathBuf;
+
+/// Held until dropped
+pub struct PoolLock {
+    _file: File,
+}
+
+/// Directory holding the lock files: `ZOOP_LOCK_DIR`, or `/run/zoop`
+fn lock_dir() -> PathBuf {
+    std::env::var_os("ZOOP_LOCK_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/run/zoop"))
+}
+
+fn open(pool: &str) -> io::Result<File> {
+    let dir = lock_dir();
+    fs::create_dir_all(&dir)?;
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(dir.join(format!("{}.lock", pool)))
+}
+
+/// The pool `dataset` is in
+pub fn pool_of(dataset: &str) -> &str {
+    dataset.split(['/', '@', '#']).next().unwrap_or(dataset)
+}
+
+/// Mark the pools containing `datasets` as being replicated, waiting for any maintenance
+/// holding them to finish deciding what to do.
+pub fn replicating(datasets: &[&str]) -> io::Result<Vec<PoolLock>> {
+    let mut pools: Vec<&str> = datasets.iter().map(|d| pool_of(d)).collect();
+    pools.sort_unstable();
+    pools.dedup();
+
+    let mut locks = Vec::new();
+    for pool in pools {
+        let file = open(pool)?;
+        file.lock_shared()?;
+        locks.push(PoolLock { _file: file });
+    }
+    Ok(locks)
+}
+
+/// Lock `pool` for maintenance. Returns `None` if it is being replicated.
+pub fn try_maintain(pool: &str) -> io::Result<Option<PoolLock>> {
+    let file = open(pool)?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(PoolLock { _file: file })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
@@ -7,49 +7,14 @@ extern crate clap;
 extern crate zfs_cmd_api;
 extern crate zoop;
 
-use clap::Parser;
 use clap::{AppSettings, Arg, SubCommand};
 use std::io::Write;
 use zfs_cmd_api::Zfs;
 use zoop::*;
 
-#[derive(Parser, Debug)]
-struct Opts {
-    /// Do not execute anything which would change system state. Print what state would be changed
-    #[clap(short = "n")]
-    dry_run: bool,
-
-    /// emit extra info
-    #[clap(short = "v")]
-    verbose: bool,
-
-    #[clap(subcommand)]
-    subcommand: Command,
-}
-
-#[derive(Debug, Parser)]
-enum Command {
-    /// replicate an entire set of snapshots from one tree to another
-    Zcopy(ZcopyOpts),
-}
-
-#[derive(Debug, Parser)]
-struct ZcopyOpts {
-    /// Also examine datasets that decend from the specified dataset
-    #[clap(short = "r")]
-    recusive: bool,
-
-    /// Do not enable resumable send/recv when receiving
-    #[clap(short = "Y")]
-    not_resumable: bool,
-
-    src_dataset: PathBuf,
-    dest_dataset: PathBuf,
-}
-
 // hack to try to get `app_from_crate!()` to regenerate.
 #[allow(dead_code)]
-const CARGO_TOML: &'static str = include_str!("../Cargo.toml");
+const CARGO_TOML: &str = include_str!("../Cargo.toml");
 
 fn level_to_msg_prefix(level: log::Level) -> &'static str {
     use log::Level;
@@ -88,6 +53,10 @@ fn main() {
                  .short("Y")
                  .help("Do not enable resumable send/recv when receiving")
                  )
+            .arg(Arg::with_name("checkpoint")
+                 .short("C")
+                 .help("Checkpoint DEST_DATASET's pool (with the local zpool, so DEST_ZFS_CMD must also be local) first, and discard the checkpoint once the copy is verified")
+                 )
             // this matches zxfer style behavior
             /*
             .arg(Arg::with_name("preseve-path")
@@ -127,6 +96,111 @@ fn main() {
         //
         // examine snapshots & delete some of them
         // subcommand(SubCommand::with_name("snap-cleanup")
+            )
+        .subcommand(SubCommand::with_name("delegate")
+            .about("Delegate the permissions needed to zcopy from SRC_DATASET to DEST_DATASET to USER")
+            .arg(Arg::with_name("check")
+                 .short("c")
+                 .help("Only report missing permissions, do not grant them")
+                 )
+            .arg(Arg::with_name("USER")
+                 .index(1)
+                 .required(true)
+                 )
+            .arg(Arg::with_name("SRC_DATASET")
+                 .index(2)
+                 .required(true)
+                 )
+            .arg(Arg::with_name("DEST_DATASET")
+                 .index(3)
+                 .required(true)
+                 )
+            )
+        .subcommand(SubCommand::with_name("diff")
+            .about("Summarize changes between the last two snapshots replicated from SRC_DATASET to DEST_DATASET")
+            .arg(Arg::with_name("SRC_DATASET")
+                 .index(1)
+                 .required(true)
+                 )
+            .arg(Arg::with_name("DEST_DATASET")
+                 .index(2)
+                 .required(true)
+                 )
+            )
+        .subcommand(SubCommand::with_name("scrub")
+            .about("Scrub pools which have not completed a scrub or resilver within INTERVAL days, skipping those being replicated by zcopy")
+            .arg(Arg::with_name("interval")
+                 .short("i")
+                 .takes_value(true)
+                 .value_name("INTERVAL")
+                 .default_value("30")
+                 .help("Minimum days between scrubs of a pool")
+                 )
+            .arg(Arg::with_name("POOL")
+                 .index(1)
+                 .multiple(true)
+                 .help("Pools to consider (default: all pools)")
+                 )
+            )
+        .subcommand(SubCommand::with_name("rotate")
+            .about("Import whichever backup disk's pool is attached, zcopy each DATASET into it, and export it")
+            .arg(Arg::with_name("guid")
+                 .short("g")
+                 .takes_value(true)
+                 .value_name("GUID")
+                 .multiple(true)
+                 .number_of_values(1)
+                 .required(true)
+                 .help("Pool guid of a backup disk")
+                 )
+            .arg(Arg::with_name("search-dir")
+                 .short("d")
+                 .takes_value(true)
+                 .value_name("DIR")
+                 .multiple(true)
+                 .number_of_values(1)
+                 .help("Search DIR for pools instead of the default device directories")
+                 )
+            .arg(Arg::with_name("altroot")
+                 .short("R")
+                 .takes_value(true)
+                 .value_name("ALTROOT")
+                 .default_value("/mnt")
+                 .help("Mount the backup pool's filesystems beneath ALTROOT")
+                 )
+            .arg(Arg::with_name("state-dir")
+                 .short("S")
+                 .takes_value(true)
+                 .value_name("STATE_DIR")
+                 .default_value("/var/lib/zoop/rotate")
+                 .help("Record each disk's last sync in STATE_DIR")
+                 )
+            .arg(Arg::with_name("scrub")
+                 .short("s")
+                 .takes_value(true)
+                 .value_name("DAYS")
+                 .help("Scrub the backup pool before exporting it if not scrubbed within DAYS days")
+                 )
+            .arg(Arg::with_name("not-resumeable")
+                 .short("Y")
+                 .help("Do not enable resumable send/recv when receiving")
+                 )
+            .arg(Arg::with_name("checkpoint")
+                 .short("C")
+                 .help("Checkpoint the backup pool before copying, and discard the checkpoint once the copy is verified")
+                 )
+            .arg(Arg::with_name("DATASET")
+                 .index(1)
+                 .multiple(true)
+                 .required(true)
+                 )
+            )
+        .subcommand(SubCommand::with_name("history")
+            .about("Show when DATASET (or its descendants, snapshots, and bookmarks) was changed, and by which command")
+            .arg(Arg::with_name("DATASET")
+                 .index(1)
+                 .required(true)
+                 )
             ).get_matches();
 
     let dry_run = matches.occurrences_of("dry-run") > 0;
@@ -134,8 +208,8 @@ fn main() {
     let not_resumable = matches.occurrences_of("not-resumable") > 0;
 
     let opts = ZcopyOpts {
-        dry_run: dry_run,
-        verbose: verbose,
+        dry_run,
+        verbose,
         resumable: !not_resumable,
     };
 
@@ -149,17 +223,139 @@ fn main() {
         let src_zfs = Zfs::from_env_prefix("SRC");
         let dest_zfs = Zfs::from_env_prefix("DEST");
 
+        let checkpoint = matches.occurrences_of("checkpoint") > 0;
+        // `zpool` only runs locally, so it can't checkpoint a pool `DEST_ZFS_CMD` reaches elsewhere
+        if checkpoint && dest_zfs.is_remote() {
+            eprintln!("-C can't checkpoint the pool of a remote destination (DEST_ZFS_CMD={})",
+                std::env::var("DEST_ZFS_CMD").unwrap_or_default());
+            std::process::exit(1);
+        }
+
         println!(
             "copy from {} to {} (recursive={})",
             src_dataset, dest_dataset, recursive
         );
         println!("dry_run: {}", dry_run);
 
-        if recursive {
+        match delegate::check_delegation(&src_zfs, &dest_zfs, None, src_dataset, dest_dataset) {
+            Ok(missing) => {
+                for m in missing {
+                    eprintln!("WARNING: {} lacks permissions on {}: {}", m.user, m.dataset, m.permissions.join(","));
+                }
+            }
+            Err(e) => eprintln!("WARNING: could not check delegated permissions: {}", e),
+        }
+
+        if checkpoint && !dry_run {
+            let copy = || -> Result<(), String> {
+                if recursive {
+                    zcopy_recursive(&src_zfs, &dest_zfs, &opts, src_dataset, dest_dataset).map_err(|e| {
+                        let e: Vec<String> = e.iter().map(|e| e.to_string()).collect();
+                        e.join(", ")
+                    })?;
+                } else {
+                    zcopy_one(&src_zfs, &dest_zfs, &opts, src_dataset, dest_dataset)?;
+                }
+                diff::verify_replicated(&src_zfs, &dest_zfs, recursive, src_dataset, dest_dataset)
+            };
+
+            let zpool = zfs_cmd_api::zpool::ZpoolCmd::default();
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(checkpoint::guarded(&zpool, lock::pool_of(dest_dataset), copy)).unwrap();
+        } else if recursive {
             zcopy_recursive(&src_zfs, &dest_zfs, &opts, src_dataset, dest_dataset).unwrap();
         } else {
             zcopy_one(&src_zfs, &dest_zfs, &opts, src_dataset, dest_dataset).unwrap();
         }
+    } else if let Some(matches) = matches.subcommand_matches("delegate") {
+        let user = matches.value_of("USER").unwrap();
+        let src_dataset = matches.value_of("SRC_DATASET").unwrap();
+        let dest_dataset = matches.value_of("DEST_DATASET").unwrap();
+
+        let src_zfs = Zfs::from_env_prefix("SRC");
+        let dest_zfs = Zfs::from_env_prefix("DEST");
+
+        if matches.occurrences_of("check") > 0 {
+            let missing = delegate::check_delegation(&src_zfs, &dest_zfs, Some(user), src_dataset, dest_dataset).unwrap();
+            for m in missing.iter() {
+                println!("{}: missing {}", m.dataset, m.permissions.join(","));
+            }
+            if !missing.is_empty() {
+                std::process::exit(1);
+            }
+        } else {
+            delegate::delegate(&src_zfs, &dest_zfs, dry_run, user, src_dataset, dest_dataset).unwrap();
+        }
+    } else if let Some(matches) = matches.subcommand_matches("diff") {
+        let src_dataset = matches.value_of("SRC_DATASET").unwrap();
+        let dest_dataset = matches.value_of("DEST_DATASET").unwrap();
+
+        let src_zfs = Zfs::from_env_prefix("SRC");
+        let dest_zfs = Zfs::from_env_prefix("DEST");
+
+        diff::diff_last_replicated(&src_zfs, &dest_zfs, verbose, src_dataset, dest_dataset).unwrap();
+    } else if let Some(matches) = matches.subcommand_matches("scrub") {
+        let days: u64 = value_t!(matches, "interval", u64).unwrap_or_else(|e| e.exit());
+        let pools: Vec<&str> = matches.values_of("POOL").map(|v| v.collect()).unwrap_or_default();
+
+        let zpool = zfs_cmd_api::zpool::ZpoolCmd::default();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(scrub::schedule_scrubs(&zpool, dry_run, std::time::Duration::from_secs(days * 24 * 60 * 60), &pools)).unwrap();
+    } else if let Some(matches) = matches.subcommand_matches("rotate") {
+        let guids: Vec<u64> = values_t!(matches, "guid", u64).unwrap_or_else(|e| e.exit());
+        let scrub_interval = if matches.is_present("scrub") {
+            let days = value_t!(matches, "scrub", u64).unwrap_or_else(|e| e.exit());
+            Some(std::time::Duration::from_secs(days * 24 * 60 * 60))
+        } else {
+            None
+        };
+        let owned = |name| -> Vec<String> {
+            matches.values_of(name).map(|v| v.map(|s| s.to_owned()).collect()).unwrap_or_default()
+        };
+
+        let rotate_opts = rotate::RotateOpts {
+            guids,
+            search_dirs: owned("search-dir"),
+            altroot: matches.value_of("altroot").unwrap().to_owned(),
+            state_dir: matches.value_of("state-dir").unwrap().into(),
+            datasets: owned("DATASET"),
+            scrub_interval,
+            checkpoint: matches.occurrences_of("checkpoint") > 0,
+        };
+        let opts = ZcopyOpts {
+            resumable: matches.occurrences_of("not-resumeable") == 0,
+            ..opts
+        };
+
+        let src_zfs = Zfs::from_env_prefix("SRC");
+        let dest_zfs = Zfs::from_env_prefix("DEST");
+        // as with `zcopy -C`, the checkpoint is taken with the local `zpool`
+        if rotate_opts.checkpoint && dest_zfs.is_remote() {
+            eprintln!("-C can't checkpoint the pool of a remote destination (DEST_ZFS_CMD={})",
+                std::env::var("DEST_ZFS_CMD").unwrap_or_default());
+            std::process::exit(1);
+        }
+        let zpool = zfs_cmd_api::zpool::ZpoolCmd::default();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(rotate::rotate(&zpool, &src_zfs, &dest_zfs, &opts, &rotate_opts)).unwrap();
+    } else if let Some(matches) = matches.subcommand_matches("history") {
+        let dataset = matches.value_of("DATASET").unwrap();
+
+        let zpool = zfs_cmd_api::zpool::ZpoolCmd::default();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(history::report(&zpool, dataset)).unwrap();
     } else {
         println!("need a SubCommand");
     }
@@ -0,0 +1,81 @@
+//! Temporarily unmount the mounted descendants of a `zcopy` destination.
+//!
+//! `zfs recv -F` needs to unmount the destination filesystem, which fails when filesystems are
+//! mounted beneath it.
+
+use zfs_cmd_api::{Zfs, ZfsError};
+
+/// Filesystems unmounted by `unmount_descendants()`. They are mounted again by `restore()`, or
+/// (on a best effort basis) when dropped.
+pub struct Unmounted<'a> {
+    zfs: &'a Zfs,
+    /// In the order they were unmounted (deepest first)
+    datasets: Vec<String>,
+}
+
+/// Unmount every mounted descendant of `dataset` (but not `dataset` itself), deepest first.
+///
+/// If any can't be unmounted, those already unmounted are mounted again.
+pub fn unmount_descendants<'a>(zfs: &'a Zfs, dataset: &str) -> Result<Unmounted<'a>, String> {
+    let mut unmounted = Unmounted {
+        zfs,
+        datasets: Vec::new(),
+    };
+
+    let mounts = match zfs.mounts(dataset, true) {
+        Ok(v) => v,
+        Err(ZfsError::NoDataset { .. }) => return Ok(unmounted),
+        Err(e) => return Err(format!("could not list mounts of {}: {}", dataset, e)),
+    };
+
+    // listed sorted by name, so children follow their parents
+    let mut names: Vec<String> = mounts
+        .into_iter()
+        .filter(|m| m.mounted && m.name != dataset)
+        .map(|m| m.name)
+        .collect();
+    names.reverse();
+
+    for name in names {
+        println!(" unmounting {}", name);
+        match zfs.unmount(Default::default(), Some(&name)) {
+            Ok(()) => unmounted.datasets.push(name),
+            Err(ZfsError::Busy { target, .. }) => {
+                return Err(format!("could not unmount {}: {} is busy", name, target));
+            }
+            Err(e) => return Err(format!("could not unmount {}: {}", name, e)),
+        }
+    }
+
+    Ok(unmounted)
+}
+
+impl<'a> Unmounted<'a> {
+    fn remount(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+        while let Some(name) = self.datasets.pop() {
+            if let Err(e) = self.zfs.mount(Default::default(), &[], Some(&name)) {
+                errors.push(format!("could not mount {}: {}", name, e));
+            }
+        }
+        errors
+    }
+
+    /// Mount the unmounted filesystems again, parents first
+    pub fn restore(mut self) -> Result<(), String> {
+        let errors = self.remount();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join(", "))
+        }
+    }
+}
+
+impl<'a> Drop for Unmounted<'a> {
+    fn drop(&mut self) {
+        for e in self.remount() {
+            eprintln!("WARNING: {}", e);
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! Replicate to whichever of a set of removable backup disks (each holding its own pool) is
+//! attached: import its pool, `zcopy` into it, optionally scrub it, and export it again.
+//!
+//! A disk may be pulled at any point. Receives are resumable, the pool has `failmode=continue` so
+//! I/O to a missing disk fails instead of blocking, exports are forced if need be, and a disk's
+//! recorded last sync only advances once every dataset tree was copied.
+
+use crate::checkpoint::guarded;
+use crate::diff::verify_replicated;
+use crate::{zcopy_recursive, ZcopyOpts};
+use enumflags2::BitFlags;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zfs_cmd_api::maintenance::{scrub_due, ScrubAction, ScrubDecision};
+use zfs_cmd_api::status::VdevState;
+use zfs_cmd_api::wait::PoolActivity;
+use zfs_cmd_api::zpool::{ImportOptions, PoolProperty, ZpoolCmd};
+use zfs_cmd_api::Zfs;
+
+pub struct RotateOpts {
+    /// Guids of the backup disks' pools
+    pub guids: Vec<u64>,
+    /// Directories to search for pools (`zpool import -d`). If empty, the defaults are used.
+    pub search_dirs: Vec<String>,
+    /// Filesystems of an imported backup pool are mounted beneath this directory
+    pub altroot: String,
+    /// Where the per-disk state is recorded
+    pub state_dir: PathBuf,
+    /// Dataset trees to copy. Each is received as `<backup pool>/<dataset>`.
+    pub datasets: Vec<String>,
+    /// Scrub the backup pool (waiting for it to finish) if it hasn't completed a scan within
+    /// this interval
+    pub scrub_interval: Option<Duration>,
+    /// Checkpoint the backup pool before copying, discarding the checkpoint once every dataset
+    /// tree was copied and verified
+    pub checkpoint: bool,
+}
+
+/// What is recorded about each backup disk, in `<state_dir>/<guid>`
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct DiskState {
+    pub pool: String,
+    /// Seconds since the epoch
+    pub last_attempt: Option<u64>,
+    /// When every dataset tree was last copied successfully
+    pub last_sync: Option<u64>,
+    /// `ok`, or the reason the last attempt failed
+    pub result: String,
+}
+
+impl DiskState {
+    /// Parse `key=value` lines, ignoring unknown keys
+    pub fn parse(text: &str) -> Self {
+        let mut state = DiskState::default();
+        for line in text.lines() {
+            let (k, v) = match line.split_once('=') {
+                Some(kv) => kv,
+                None => continue,
+            };
+            match k {
+                "pool" => state.pool = v.to_owned(),
+                "last_attempt" => state.last_attempt = v.parse().ok(),
+                "last_sync" => state.last_sync = v.parse().ok(),
+                "result" => state.result = v.to_owned(),
+                _ => {}
+            }
+        }
+        state
+    }
+
+    pub fn format(&self) -> String {
+        let mut out = format!("pool={}\n", self.pool);
+        if let Some(t) = self.last_attempt {
+            out.push_str(&format!("last_attempt={}\n", t));
+        }
+        if let Some(t) = self.last_sync {
+            out.push_str(&format!("last_sync={}\n", t));
+        }
+        out.push_str(&format!("result={}\n", self.result.replace('\n', " ")));
+        out
+    }
+
+    pub fn load(state_dir: &Path, guid: u64) -> io::Result<Self> {
+        match fs::read_to_string(state_dir.join(guid.to_string())) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replace the recorded state, atomically
+    pub fn save(&self, state_dir: &Path, guid: u64) -> io::Result<()> {
+        fs::create_dir_all(state_dir)?;
+        let path = state_dir.join(guid.to_string());
+        let tmp = state_dir.join(format!(".{}.tmp", guid));
+        fs::write(&tmp, self.format())?;
+        fs::rename(&tmp, &path)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Find the attached backup disks (by pool guid), and copy to each in turn.
+///
+/// Finding no disk attached is not an error.
+pub async fn rotate(
+    zpool: &ZpoolCmd,
+    src_zfs: &Zfs,
+    dest_zfs: &Zfs,
+    zcopy_opts: &ZcopyOpts,
+    opts: &RotateOpts,
+) -> Result<(), String> {
+    // a previous run may have been interrupted before exporting
+    let imported = zpool
+        .list_parsable(None)
+        .await
+        .map_err(|e| format!("could not list pools: {}", e))?;
+    let mut disks: Vec<(u64, String, bool)> = imported
+        .pools
+        .values()
+        .filter_map(|p| {
+            let guid: u64 = p.pool_guid.parse().ok()?;
+            if opts.guids.contains(&guid) {
+                Some((guid, p.name.clone(), true))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let dirs: Vec<&str> = opts.search_dirs.iter().map(|d| d.as_str()).collect();
+    let importable = zpool
+        .import_discover(&dirs, false)
+        .await
+        .map_err(|e| format!("could not search for pools: {}", e))?;
+    for p in importable {
+        if opts.guids.contains(&p.id) && !disks.iter().any(|(g, _, _)| *g == p.id) {
+            disks.push((p.id, p.pool.name, false));
+        }
+    }
+
+    if disks.is_empty() {
+        println!("rotate: no backup disk attached");
+        return Ok(());
+    }
+
+    let mut failed = Vec::new();
+    for (guid, pool, is_imported) in disks {
+        let mut state = DiskState::load(&opts.state_dir, guid)
+            .map_err(|e| format!("could not read state of {}: {}", guid, e))?;
+        state.pool = pool.clone();
+        state.last_attempt = Some(now());
+
+        let result = rotate_one(
+            zpool,
+            src_zfs,
+            dest_zfs,
+            zcopy_opts,
+            opts,
+            guid,
+            &pool,
+            is_imported,
+        )
+        .await;
+
+        if zcopy_opts.dry_run {
+            continue;
+        }
+
+        match result {
+            Ok(()) => {
+                state.last_sync = state.last_attempt;
+                state.result = "ok".to_owned();
+            }
+            Err(ref e) => {
+                state.result = e.clone();
+                failed.push(format!("{} ({}): {}", pool, guid, e));
+            }
+        }
+        if let Err(e) = state.save(&opts.state_dir, guid) {
+            failed.push(format!("could not record state of {}: {}", guid, e));
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(failed.join("; "))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn rotate_one(
+    zpool: &ZpoolCmd,
+    src_zfs: &Zfs,
+    dest_zfs: &Zfs,
+    zcopy_opts: &ZcopyOpts,
+    opts: &RotateOpts,
+    guid: u64,
+    pool: &str,
+    is_imported: bool,
+) -> Result<(), String> {
+    if zcopy_opts.dry_run {
+        for dataset in opts.datasets.iter() {
+            println!("rotate: would copy {} to {}/{}", dataset, pool, dataset);
+        }
+        return Ok(());
+    }
+
+    let failmode = PoolProperty::new("failmode", "continue");
+    let prepared = if !is_imported {
+        println!("rotate: importing {} ({})", pool, guid);
+        let import = ImportOptions {
+            dirs: opts.search_dirs.clone(),
+            altroot: Some(opts.altroot.clone()),
+            properties: vec![failmode],
+            ..Default::default()
+        };
+        zpool
+            .import(&guid.to_string(), None, &import)
+            .await
+            .map_err(|e| format!("import failed: {}", e))?;
+        Ok(())
+    } else {
+        // left imported by an interrupted run (or by hand), possibly with another failmode
+        zpool
+            .set(pool, &failmode)
+            .await
+            .map_err(|e| format!("could not set failmode: {}", e))
+    };
+
+    let result = match prepared {
+        Ok(()) => copy_and_scrub(zpool, src_zfs, dest_zfs, zcopy_opts, opts, pool).await,
+        Err(e) => Err(e),
+    };
+
+    // export even when copying failed, so the disk can be removed
+    println!("rotate: exporting {}", pool);
+    let exported = match zpool.export(pool, false).await {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            // filesystems on a pulled disk can't be unmounted cleanly
+            eprintln!("rotate: exporting {} failed, forcing: {}", pool, e);
+            zpool
+                .export(pool, true)
+                .await
+                .map_err(|e| format!("export failed: {}", e))
+        }
+    };
+
+    result.and(exported)
+}
+
+async fn copy_and_scrub(
+    zpool: &ZpoolCmd,
+    src_zfs: &Zfs,
+    dest_zfs: &Zfs,
+    zcopy_opts: &ZcopyOpts,
+    opts: &RotateOpts,
+    pool: &str,
+) -> Result<(), String> {
+    // the disk may have failed, or been pulled, since the pool was imported
+    let status = zpool
+        .status(Some(pool))
+        .await
+        .map_err(|e| format!("could not get status: {}", e))?;
+    let status = status
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("no status reported for {}", pool))?;
+    match status.state {
+        VdevState::Online | VdevState::Degraded => {}
+        ref state => return Err(format!("pool is {:?}, not copying", state)),
+    }
+
+    let copy = || -> Result<(), String> {
+        let mut errors = Vec::new();
+        for dataset in opts.datasets.iter() {
+            let dest = format!("{}/{}", pool, dataset);
+
+            // `recv` creates `dest`, but not its parents
+            if let Some((parent, _)) = dest.rsplit_once('/') {
+                if parent != pool {
+                    let flags = BitFlags::default()
+                        | zfs_cmd_api::CreateFlags::CreateParents
+                        | zfs_cmd_api::CreateFlags::NoMount;
+                    if let Err(e) = dest_zfs.create(flags, &[("canmount", "off")], parent) {
+                        errors.push(format!("could not create {}: {}", parent, e));
+                        continue;
+                    }
+                }
+            }
+
+            if let Err(e) = zcopy_recursive(src_zfs, dest_zfs, zcopy_opts, dataset, &dest) {
+                let e: Vec<String> = e.iter().map(|e| e.to_string()).collect();
+                errors.push(format!("zcopy {} failed: {}", dataset, e.join(", ")));
+            } else if opts.checkpoint {
+                if let Err(e) = verify_replicated(src_zfs, dest_zfs, true, dataset, &dest) {
+                    errors.push(format!("verifying {} failed: {}", dataset, e));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    };
+    if opts.checkpoint {
+        guarded(zpool, pool, copy).await?;
+    } else {
+        copy()?;
+    }
+
+    let interval = match opts.scrub_interval {
+        Some(i) => i,
+        None => return Ok(()),
+    };
+    // copying doesn't start a scan, so the status from before it still applies
+    match scrub_due(&status, SystemTime::now(), interval) {
+        ScrubDecision::NotDue(_) => return Ok(()),
+        ScrubDecision::Due => {
+            println!("rotate: scrubbing {}", pool);
+            zpool
+                .scrub(pool, ScrubAction::Start, false)
+                .await
+                .map_err(|e| format!("could not start scrub: {}", e))?;
+        }
+        // an interrupted scrub resumes on import
+        ScrubDecision::InProgress(_) => println!("rotate: waiting for scan of {}", pool),
+    }
+    zpool
+        .wait(
+            pool,
+            &[PoolActivity::Scrub, PoolActivity::Resilver],
+            None,
+            None,
+            |_| {},
+        )
+        .await
+        .map_err(|e| format!("waiting for scrub failed: {}", e))?;
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Scrub pools which haven't completed a scan recently, replacing fixed schedules that know
+//! nothing about replication.
+
+use crate::lock;
+use std::time::{Duration, SystemTime};
+use zfs_cmd_api::maintenance::{scrub_due, ScrubAction, ScrubDecision};
+use zfs_cmd_api::status::PoolStatus;
+use zfs_cmd_api::zpool::ZpoolCmd;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Start a scrub of each of `pools` (or, if empty, of every pool) which hasn't completed a scan
+/// within `min_interval`.
+///
+/// Pools which are already being scanned, or which a `zcopy` is replicating to or from, are
+/// skipped; a later run will scrub them.
+pub async fn schedule_scrubs(
+    zpool: &ZpoolCmd,
+    dry_run: bool,
+    min_interval: Duration,
+    pools: &[&str],
+) -> Result<(), String> {
+    let mut statuses: Vec<PoolStatus> = Vec::new();
+    if pools.is_empty() {
+        statuses = zpool
+            .status(None)
+            .await
+            .map_err(|e| format!("could not get pool status: {}", e))?;
+    }
+    for pool in pools {
+        statuses.extend(
+            zpool
+                .status(Some(pool))
+                .await
+                .map_err(|e| format!("could not get status of {}: {}", pool, e))?,
+        );
+    }
+
+    let now = SystemTime::now();
+    let mut failed = Vec::new();
+    for status in statuses {
+        let pool = &status.name;
+        match scrub_due(&status, now, min_interval) {
+            ScrubDecision::Due => {}
+            ScrubDecision::InProgress(function) => {
+                println!("{}: {:?} in progress, skipping", pool, function);
+                continue;
+            }
+            ScrubDecision::NotDue(next) => {
+                let wait = next.duration_since(now).unwrap_or_default();
+                println!("{}: next scrub due in {} days", pool, wait.as_secs() / DAY);
+                continue;
+            }
+        }
+
+        // held while starting the scrub so a `zcopy` can't begin in between
+        let _lock = match lock::try_maintain(pool) {
+            Ok(Some(l)) => l,
+            Ok(None) => {
+                println!("{}: replication in progress, skipping", pool);
+                continue;
+            }
+            Err(e) => {
+                // without the lock, a `zcopy` could be running
+                failed.push(format!("{}: could not lock: {}", pool, e));
+                continue;
+            }
+        };
+
+        if dry_run {
+            println!("{}: would start scrub", pool);
+            continue;
+        }
+
+        println!("{}: starting scrub", pool);
+        if let Err(e) = zpool.scrub(pool, ScrubAction::Start, false).await {
+            failed.push(format!("{}: {}", pool, e));
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("could not start scrubs: {}", failed.join("; ")))
+    }
+}
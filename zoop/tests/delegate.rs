@@ -0,0 +1,119 @@
+extern crate zfs_cmd_api;
+extern crate zoop;
+
+use std::os::unix::fs::PermissionsExt;
+use zfs_cmd_api::Zfs;
+use zoop::delegate::{check_delegation, delegate};
+
+/// A `zfs` on which nothing is delegated and only `backup/new` (and its descendants) don't
+/// exist, which records its arguments (one invocation per line) in the returned log
+fn fake_zfs(name: &str) -> (Zfs, std::path::PathBuf) {
+    let dir = std::env::temp_dir().join(format!("zfs-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("args");
+    let _ = std::fs::remove_file(&log);
+    let script = dir.join("zfs");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\n\
+             echo \"$@\" >> {}\n\
+             case \"$#:$2\" in\n\
+             2:backup/new|2:backup/new/*)\n\
+             echo \"cannot open '$2': dataset does not exist\" >&2; exit 1 ;;\n\
+             esac\n",
+            log.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let var = format!("DELEGATE_{}", name.to_uppercase());
+    std::env::set_var(format!("{}_ZFS_CMD", var), &script);
+    (Zfs::from_env_prefix(&var), log)
+}
+
+#[test]
+fn check_missing_dest() {
+    let (zfs, _) = fake_zfs("check");
+    let missing =
+        check_delegation(&zfs, &zfs, Some("nobody"), "tank/home", "backup/new/home").unwrap();
+    assert_eq!(missing.len(), 2);
+    assert_eq!(missing[0].dataset, "tank/home");
+    assert_eq!(missing[1].dataset, "backup");
+}
+
+#[test]
+fn delegate_missing_dest() {
+    let (zfs, log) = fake_zfs("grant");
+    delegate(&zfs, &zfs, false, "nobody", "tank/home", "backup/new/home").unwrap();
+
+    let args = std::fs::read_to_string(&log).unwrap();
+    assert_eq!(
+        args.lines().collect::<Vec<_>>(),
+        [
+            "allow tank/home",
+            "allow backup/new/home",
+            "allow backup/new",
+            "allow backup",
+            "allow -u nobody send,hold,bookmark tank/home",
+            "allow -u nobody receive,create,mount,destroy backup",
+        ]
+    );
+}
+
+/// A `zfs` reached through a `host` script, as `ssh host zfs` would be, where `id` reports the
+/// user `backup` in the group `replication`, which holds every permission through nested sets
+fn host_zfs(name: &str) -> Zfs {
+    let dir = std::env::temp_dir().join(format!("zfs-{}-{}", std::process::id(), name));
+    let bin = dir.join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    let scripts = [
+        (
+            dir.join("host"),
+            format!("#!/bin/sh\nPATH={}:$PATH exec \"$@\"\n", bin.display()),
+        ),
+        (
+            bin.join("id"),
+            "#!/bin/sh\n\
+             case \"$1\" in\n\
+             -un) echo backup ;;\n\
+             -Gn) [ \"$2\" = backup ] && echo backup replication ;;\n\
+             esac\n"
+                .to_owned(),
+        ),
+        (
+            bin.join("zfs"),
+            "#!/bin/sh\n\
+             printf -- '---- Permissions on %s ----\\n' \"$2\"\n\
+             printf 'Permission sets:\\n\\t@send send,hold,bookmark\\n'\n\
+             printf '\\t@recv receive,create,mount,destroy\\n\\t@zcopy @send,@recv\\n'\n\
+             printf 'Local+Descendent permissions:\\n\\tgroup replication @zcopy\\n'\n"
+                .to_owned(),
+        ),
+    ];
+    for (path, script) in scripts.iter() {
+        std::fs::write(path, script).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let var = format!("DELEGATE_{}", name.to_uppercase());
+    std::env::set_var(
+        format!("{}_ZFS_CMD", var),
+        format!("{} zfs", dir.join("host").display()),
+    );
+    Zfs::from_env_prefix(&var)
+}
+
+#[test]
+fn check_on_host() {
+    let zfs = host_zfs("host");
+    assert_eq!(
+        check_delegation(&zfs, &zfs, None, "tank/home", "backup/home").unwrap(),
+        []
+    );
+    // `nobody` isn't in `replication` there, whatever it is a member of here
+    let missing = check_delegation(&zfs, &zfs, Some("nobody"), "tank/home", "backup/home").unwrap();
+    assert_eq!(missing.len(), 2);
+    assert_eq!(missing[0].user, "nobody");
+}
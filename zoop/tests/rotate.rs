@@ -0,0 +1,60 @@
+extern crate zoop;
+
+use zoop::rotate::DiskState;
+
+#[test]
+fn parse() {
+    let state = DiskState::parse(
+        "pool=backup1\n\
+         last_attempt=1760757000\n\
+         last_sync=1760750000\n\
+         unknown=ignored\n\
+         not a key value line\n\
+         result=zcopy tank failed: a=b\n",
+    );
+    assert_eq!(
+        state,
+        DiskState {
+            pool: "backup1".to_owned(),
+            last_attempt: Some(1760757000),
+            last_sync: Some(1760750000),
+            result: "zcopy tank failed: a=b".to_owned(),
+        }
+    );
+
+    // never synced, or an unreadable time
+    let state = DiskState::parse("pool=backup2\nlast_sync=soon\nresult=ok\n");
+    assert_eq!(state.last_attempt, None);
+    assert_eq!(state.last_sync, None);
+    assert_eq!(DiskState::parse(""), DiskState::default());
+}
+
+#[test]
+fn format() {
+    let state = DiskState {
+        pool: "backup1".to_owned(),
+        last_attempt: Some(1760757000),
+        last_sync: None,
+        result: "export failed: cannot export 'backup1':\npool is busy".to_owned(),
+    };
+    let text = state.format();
+    assert_eq!(
+        text,
+        "pool=backup1\n\
+         last_attempt=1760757000\n\
+         result=export failed: cannot export 'backup1': pool is busy\n"
+    );
+
+    // newlines in the result are flattened, so it survives a round trip as one line
+    let parsed = DiskState::parse(&text);
+    assert_eq!(parsed.last_attempt, state.last_attempt);
+    assert_eq!(parsed.result, state.result.replace('\n', " "));
+
+    let ok = DiskState {
+        pool: "backup2".to_owned(),
+        last_attempt: Some(2),
+        last_sync: Some(2),
+        result: "ok".to_owned(),
+    };
+    assert_eq!(DiskState::parse(&ok.format()), ok);
+}
@@ -0,0 +1,39 @@
+extern crate zfs_cmd_api;
+extern crate zoop;
+
+use std::os::unix::fs::PermissionsExt;
+use zfs_cmd_api::Zfs;
+use zoop::{zcopy_one, ZcopyOpts};
+
+/// A `zfs` where `tank/home` has one snapshot, `backup` has no datasets, and every send fails
+/// as it would once the disk it reads from is gone
+fn fake_zfs(name: &str) -> Zfs {
+    let dir = std::env::temp_dir().join(format!("zcopy-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("zfs");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\n\
+         for last; do :; done\n\
+         case \"$1:$last\" in\n\
+         list:tank/home) printf '1\\ttank/home@a\\t123\\tsnapshot\\n' ;;\n\
+         list:*) echo \"cannot open '$last': dataset does not exist\" >&2; exit 1 ;;\n\
+         send:*) echo 'cannot send: I/O error' >&2; exit 1 ;;\n\
+         recv:*|receive:*) cat > /dev/null; exit 1 ;;\n\
+         esac\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    std::env::set_var("ZOOP_LOCK_DIR", &dir);
+    let var = format!("ZCOPY_{}", name.to_uppercase());
+    std::env::set_var(format!("{}_ZFS_CMD", var), &script);
+    Zfs::from_env_prefix(&var)
+}
+
+#[test]
+fn send_failure() {
+    let zfs = fake_zfs("send_failure");
+    let e = zcopy_one(&zfs, &zfs, &ZcopyOpts::default(), "tank/home", "backup/home").unwrap_err();
+    assert!(e.contains("tank/home@a"), "{}", e);
+}